@@ -0,0 +1,82 @@
+//! Generating, placing bombs on and flood filling large boards. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, SeedableRng};
+use game_clones::minesweeper::board::{build_minefield_with, Board, BoardOptions, TileValue};
+
+const SEED: u64 = 7;
+/// Sides of the square boards, up to a million tiles.
+const SIDES: [u32; 3] = [100, 300, 1000];
+/// Shares of the tiles that get a bomb, in percent, up to a board that is all bombs.
+const DENSITIES: [u32; 6] = [1, 10, 25, 50, 85, 100];
+
+/// A percent of the tiles are bombs, so that a click on an empty tile opens most of the board.
+fn sparse(side: u32) -> Board {
+    Board::seeded(side, side, side * side / 100, BoardOptions::default(), SEED).unwrap()
+}
+
+fn first_empty(board: &Board) -> (usize, usize) {
+    (0..board.tile_rows as usize)
+        .flat_map(|i| (0..board.tile_columns as usize).map(move |j| (i, j)))
+        .find(|&(i, j)| board.tile(i, j).value() == TileValue::Adjacent(0))
+        .unwrap()
+}
+
+fn generate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate");
+    group.sample_size(10);
+    for side in SIDES {
+        for (name, bombs) in [("sparse", side * side / 100), ("dense", side * side / 5)] {
+            group.bench_with_input(BenchmarkId::new(name, side), &side, |b, &side| {
+                b.iter(|| Board::seeded(side, side, bombs, BoardOptions::default(), SEED).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Placing the bombs alone, on a board of 250 000 tiles.
+fn place(c: &mut Criterion) {
+    let mut group = c.benchmark_group("place");
+    group.sample_size(10);
+    let side = 500;
+    for density in DENSITIES {
+        let bombs = side * side * density / 100;
+        group.bench_with_input(BenchmarkId::from_parameter(density), &bombs, |b, &bombs| {
+            let mut rng = StdRng::seed_from_u64(SEED);
+            b.iter(|| build_minefield_with(&mut rng, side, side, bombs, BoardOptions::default(), None, &[]).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn flood_fill(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flood_fill");
+    group.sample_size(10);
+    for side in SIDES {
+        let board = sparse(side);
+        let (i, j) = first_empty(&board);
+        group.bench_with_input(BenchmarkId::from_parameter(side), &side, |b, &side| {
+            b.iter_batched(|| sparse(side), |mut board| {
+                board.resolve_click(i, j);
+                board
+            }, BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    group.sample_size(10);
+    for side in SIDES {
+        let board = sparse(side);
+        group.bench_with_input(BenchmarkId::from_parameter(side), &board, |b, board| {
+            b.iter(|| serde_json::to_string(board).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, generate, place, flood_fill, serialize);
+criterion_main!(benches);
@@ -0,0 +1,233 @@
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use sdl2::{
+    image::LoadTexture,
+    pixels::{Color, PixelFormatEnum},
+    render::{Canvas, Texture, TextureCreator},
+    surface::Surface,
+    video::{Window, WindowContext},
+    EventPump,
+    Sdl,
+    VideoSubsystem,
+};
+
+/// Most fixed steps simulated in one frame. After a longer stall the game slows down for a moment
+/// instead of jumping ahead.
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+pub struct WindowSettings {
+    pub title: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+}
+
+/// A borderless window with a vsynced canvas, and the SDL pieces that go with it.
+pub struct App {
+    pub context: Sdl,
+    pub video: VideoSubsystem,
+    pub canvas: Canvas<Window>,
+    pub event_pump: EventPump,
+}
+
+/// Whether the main loop keeps going after a frame.
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+impl App {
+    pub fn new(settings: WindowSettings) -> Result<Self, String> {
+        Self::build(settings, false)
+    }
+
+    /// An app whose window is never shown, drawn by SDL's software renderer on the dummy video
+    /// driver. It works without a display, for screenshots and rendering tests.
+    pub fn headless(settings: WindowSettings) -> Result<Self, String> {
+        sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+        Self::build(settings, true)
+    }
+
+    fn build(settings: WindowSettings, headless: bool) -> Result<Self, String> {
+        let context = sdl2::init()?;
+        let video = context.video()?;
+
+        let mut builder = video.window(settings.title, settings.width, settings.height);
+        builder.position_centered();
+        if settings.resizable {
+            builder.resizable();
+        }
+        if headless {
+            builder.hidden();
+        }
+        let mut window = builder.build().map_err(|e| e.to_string())?;
+        window.set_bordered(false);
+
+        let canvas = window.into_canvas().target_texture();
+        let mut canvas = match headless {
+            true => canvas.software(),
+            false => canvas.present_vsync(),
+        }
+        .build()
+        .map_err(|e| e.to_string())?;
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.present();
+
+        let event_pump = context.event_pump()?;
+        Ok(Self { context, video, canvas, event_pump })
+    }
+
+    /// Reads back everything drawn since the last present, as a surface that can be compared to
+    /// another or saved as a PNG.
+    pub fn screenshot(&self) -> Result<Surface<'static>, String> {
+        let (width, height) = self.canvas.output_size()?;
+        let format = PixelFormatEnum::RGB24;
+        let pixels = self.canvas.read_pixels(None, format)?;
+        let mut surface = Surface::new(width, height, format)?;
+        let pitch = surface.pitch() as usize;
+        let row = width as usize * format.byte_size_per_pixel();
+        surface.with_lock_mut(|target| {
+            // the surface's rows may be padded, the pixels read back are not
+            for (y, source) in pixels.chunks_exact(row).enumerate() {
+                target[y * pitch..y * pitch + row].copy_from_slice(source);
+            }
+        });
+        Ok(surface)
+    }
+
+    /// Calls `frame` until it returns `Flow::Quit` or an error. Besides the app it gets the number
+    /// of steps of length `step` to simulate, which is 0 on frames that come faster than that.
+    pub fn run(&mut self, step: Duration, mut frame: impl FnMut(&mut Self, u32) -> Result<Flow, String>) -> Result<(), String> {
+        let mut timestep = FixedTimestep::new(step);
+        loop {
+            let steps = timestep.advance();
+            if let Flow::Quit = frame(self, steps)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Keeps game time apart from the frame rate, so a game runs at the same speed on any display.
+pub struct FixedTimestep {
+    step: Duration,
+    last: Instant,
+    pending: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration) -> Self {
+        Self { step, last: Instant::now(), pending: Duration::ZERO }
+    }
+
+    /// Number of whole steps that have passed since the last call.
+    pub fn advance(&mut self) -> u32 {
+        let now = Instant::now();
+        self.pending += now - self.last;
+        self.last = now;
+        let steps = (self.pending.as_nanos() / self.step.as_nanos().max(1)) as u32;
+        if steps > MAX_STEPS_PER_FRAME {
+            self.pending = Duration::ZERO;
+            return MAX_STEPS_PER_FRAME;
+        }
+        self.pending -= self.step * steps;
+        steps
+    }
+}
+
+/// The screens of a game, like a game over screen on top of the game it ended. Only the top one is
+/// active, and the stack derefs to it so it reads like a single state.
+pub struct SceneStack<S> {
+    scenes: Vec<S>,
+}
+
+impl<S> SceneStack<S> {
+    pub fn new(first: S) -> Self {
+        Self { scenes: vec![first] }
+    }
+
+    pub fn push(&mut self, scene: S) {
+        self.scenes.push(scene);
+    }
+
+    /// Goes back to the scene below the top one. The last scene is never popped.
+    pub fn pop(&mut self) -> Option<S> {
+        match self.scenes.len() {
+            1 => None,
+            _ => self.scenes.pop(),
+        }
+    }
+
+    /// Drops every scene and starts over with `scene`.
+    pub fn reset(&mut self, scene: S) {
+        self.scenes.clear();
+        self.scenes.push(scene);
+    }
+
+    /// The scene right below the top one.
+    pub fn below(&self) -> Option<&S> {
+        self.scenes.iter().rev().nth(1)
+    }
+}
+
+impl<S> Deref for SceneStack<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        self.scenes.last().expect("scene stack is never empty")
+    }
+}
+
+impl<S> DerefMut for SceneStack<S> {
+    fn deref_mut(&mut self) -> &mut S {
+        self.scenes.last_mut().expect("scene stack is never empty")
+    }
+}
+
+/// Directory holding the `assets` folder: the working directory when started from the repository,
+/// otherwise the one next to the executable, and the crate's own as a last resort.
+pub fn asset_root() -> &'static Path {
+    static ROOT: OnceLock<PathBuf> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let candidates = [
+            std::env::current_dir().ok(),
+            std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)),
+            Some(PathBuf::from(env!("CARGO_MANIFEST_DIR"))),
+        ];
+        candidates
+            .into_iter()
+            .flatten()
+            .find(|dir| dir.join("assets").is_dir())
+            .unwrap_or_default()
+    })
+}
+
+/// Resolves a path like `assets/Monaco.ttf` against the asset root. Absolute paths are kept as they are.
+pub fn asset_path(path: impl AsRef<Path>) -> PathBuf {
+    asset_root().join(path)
+}
+
+/// Loads textures through a single texture creator, so everything loaded lives as long as the assets.
+pub struct Assets {
+    creator: TextureCreator<WindowContext>,
+}
+
+impl Assets {
+    pub fn new(canvas: &Canvas<Window>) -> Self {
+        Self { creator: canvas.texture_creator() }
+    }
+
+    pub fn creator(&self) -> &TextureCreator<WindowContext> {
+        &self.creator
+    }
+
+    /// Loads an image, relative to the asset root.
+    pub fn texture(&self, path: impl AsRef<Path>) -> Result<Texture<'_>, String> {
+        self.creator.load_texture(asset_path(path)).map_err(|e| e.to_string())
+    }
+}
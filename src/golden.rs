@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use sdl2::{
+    image::{LoadSurface, SaveSurface},
+    pixels::PixelFormatEnum,
+    surface::Surface,
+};
+
+/// Largest difference in a colour channel that still counts as the same colour, so that small
+/// changes in font hinting between SDL_ttf versions don't fail a comparison.
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Pixels in every thousand that may differ by more than that.
+const MAX_DIFFERENT_PER_MILLE: usize = 2;
+
+/// Compares a screenshot to the golden image at `path`. When the `UPDATE_GOLDEN` environment
+/// variable is set, the screenshot is saved as the golden image instead. A missing golden image
+/// fails the check, as does a mismatch, and both save the screenshot next to the golden image as
+/// `<name>.actual.png`.
+pub fn check(screenshot: &Surface, path: &Path) -> Result<(), String> {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        return screenshot.save(path);
+    }
+    if !path.exists() {
+        let actual_path = actual_path(path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        screenshot.save(&actual_path)?;
+        return Err(format!(
+            "{} is missing, see {} and record it with UPDATE_GOLDEN=1",
+            path.display(),
+            actual_path.display()
+        ));
+    }
+    let golden = Surface::from_file(path)?;
+    let (actual, expected) = (rgb_rows(screenshot)?, rgb_rows(&golden)?);
+    let mismatch = match (screenshot.size(), golden.size()) {
+        (actual_size, golden_size) if actual_size != golden_size => {
+            Some(format!("is {actual_size:?} pixels instead of {golden_size:?}"))
+        },
+        (size, _) => {
+            let different = actual
+                .chunks_exact(3)
+                .zip(expected.chunks_exact(3))
+                .filter(|(a, e)| a.iter().zip(e.iter()).any(|(a, e)| a.abs_diff(*e) > CHANNEL_TOLERANCE))
+                .count();
+            let pixels = (size.0 * size.1) as usize;
+            match different * 1000 > pixels * MAX_DIFFERENT_PER_MILLE {
+                true => Some(format!("differs in {different} of {pixels} pixels")),
+                false => None,
+            }
+        },
+    };
+    match mismatch {
+        None => Ok(()),
+        Some(mismatch) => {
+            let actual_path = actual_path(path);
+            screenshot.save(&actual_path)?;
+            Err(format!("{} {mismatch}, see {}", path.display(), actual_path.display()))
+        },
+    }
+}
+
+/// The pixels of a surface as tightly packed RGB rows.
+fn rgb_rows(surface: &Surface) -> Result<Vec<u8>, String> {
+    let surface = surface.convert_format(PixelFormatEnum::RGB24)?;
+    let row = surface.width() as usize * 3;
+    let pitch = surface.pitch() as usize;
+    Ok(surface.with_lock(|pixels| {
+        pixels
+            .chunks(pitch)
+            .take(surface.height() as usize)
+            .flat_map(|line| line[..row].iter().copied())
+            .collect()
+    }))
+}
+
+fn actual_path(golden: &Path) -> PathBuf {
+    let stem = golden.file_stem().unwrap_or_default().to_string_lossy();
+    golden.with_file_name(format!("{stem}.actual.png"))
+}
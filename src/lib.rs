@@ -0,0 +1,12 @@
+//! Code shared by the game binaries: a small SDL app shell, the minesweeper engine with the
+//! pieces its front-ends are built from, and space invaders. `golden` compares screenshots of
+//! them to known good images. Everything that needs SDL is behind the `sdl` feature, which is
+//! off by default.
+
+#[cfg(feature = "sdl")]
+pub mod app;
+#[cfg(feature = "sdl")]
+pub mod golden;
+pub mod minesweeper;
+#[cfg(feature = "sdl")]
+pub mod space_invaders;
@@ -1,12 +1,19 @@
-use sdl2::rect::{Point, Rect};
 use serde::{Serialize, Deserialize};
-use rand::Rng;
-use crate::{
-    TILE_SIZE,
-    GameState,
-};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::minesweeper::bits::BitSet;
+use crate::minesweeper::error::EngineError;
+use crate::minesweeper::mask::Mask;
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+/// Progress of a single game. Front-ends keep their own state for menus and such on top of this.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum GameState {
+    #[default]
+    InGame,
+    Won,
+    GameOver,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Action {
     Reveal(u32, u32),
     ToggleFlag(u32, u32),
@@ -14,44 +21,86 @@ pub enum Action {
     Quit,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TileState {
     Hidden,
     Revealed,
-    Flagged,
+    /// Flagged as holding the given number of bombs.
+    Flagged(u32),
+    Questioned,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TileValue {
-    Bomb,
+    /// Holds the given number of bombs, which is only ever more than one when cells can hold multiple bombs.
+    Bomb(u32),
+    /// Total number of bombs in the surrounding tiles.
     Adjacent(u32),
 }
 
-#[derive(Serialize, Deserialize)]
+/// How an attempt to reveal tiles turned out. A bomb going off ends the game, it isn't an error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reveal {
+    Safe,
+    Exploded,
+}
+
+/// What a chord does when the number of surrounding flags doesn't match the tile's number.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChordMismatch {
+    Ignore,
+    RevealUnflagged,
+}
+impl ChordMismatch {
+    pub fn next(&self) -> Self {
+        match self {
+            ChordMismatch::Ignore => ChordMismatch::RevealUnflagged,
+            ChordMismatch::RevealUnflagged => ChordMismatch::Ignore,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChordMismatch::Ignore => "ignore",
+            ChordMismatch::RevealUnflagged => "reveal unflagged",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Tile {
     state: TileState,
-    x: i32,
-    y: i32,
     value: TileValue,
 }
 impl Tile {
-    pub fn new_blank(x: i32, y: i32) -> Self {
+    pub fn new_blank() -> Self {
         Tile {
             state: TileState::Hidden,
-            x: x,
-            y: y,
             value: TileValue::Adjacent(0),
         }
     }
-    pub fn set_bomb(&mut self) {
-        self.value = TileValue::Bomb;
+    /// Puts one more bomb on the tile.
+    pub fn add_bomb(&mut self) {
+        self.value = TileValue::Bomb(self.bombs() + 1);
     }
     pub fn is_bomb(&self) -> bool {
         match self.value {
-            TileValue::Bomb => true,
+            TileValue::Bomb(_) => true,
             TileValue::Adjacent(_) => false,
         }
     }
+    pub fn bombs(&self) -> u32 {
+        match self.value {
+            TileValue::Bomb(count) => count,
+            TileValue::Adjacent(_) => 0,
+        }
+    }
+    /// Number of flags placed on the tile.
+    pub fn flags(&self) -> u32 {
+        match self.state {
+            TileState::Flagged(count) => count,
+            _ => 0,
+        }
+    }
     pub fn value(&self) -> TileValue {
         self.value
     }
@@ -64,262 +113,808 @@ impl Tile {
     pub fn set_state(&mut self, state: TileState) {
         self.state = state;
     }
-    pub fn rect(&self) -> Rect {
-        Rect::new(self.x, self.y, TILE_SIZE, TILE_SIZE)
-    }
-    pub fn center(&self) -> Point {
-        self.rect().center()
-    }
 }
 
-pub fn build_minefield(row_count: u32, col_count: u32, mut bomb_count: u32) -> Vec<Vec<Tile>> {
-    let mut minefield = Vec::new();
-    for i in 0..row_count {
-        let mut new_row = Vec::new();
-        for j in 0..col_count {
-            new_row.push(Tile::new_blank((j * TILE_SIZE) as i32, (i * TILE_SIZE) as i32));
+/// How the tiles of a board are arranged, which decides their neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum Topology {
+    /// Square tiles with 8 neighbours.
+    #[default]
+    Square,
+    /// Pointy topped hexagons with 6 neighbours, every odd row is shifted right by half a tile.
+    Hex,
+}
+impl Topology {
+    pub fn next(&self) -> Self {
+        match self {
+            Topology::Square => Topology::Hex,
+            Topology::Hex => Topology::Square,
         }
-        minefield.push(new_row);
     }
-    let mut rng = rand::thread_rng();
-    while bomb_count > 0 {
-        let i = rng.gen_range(0..row_count) as usize;
-        let j = rng.gen_range(0..col_count) as usize;
-        if minefield[i][j].is_bomb() {
-            continue;
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::Square => "square",
+            Topology::Hex => "hex",
         }
-        minefield[i][j].set_bomb();
-        bomb_count -= 1;
     }
 
-    for i in 0..(row_count as usize) {
-        for j in 0..(col_count as usize) {
-            let mut count = 0;
-            if minefield[i][j].is_bomb() {
+    /// Indices of all tiles surrounding `(i, j)` on a board of the given size.
+    /// With `wrap` set, tiles on opposite borders are neighbours.
+    pub fn neighbours(&self, i: usize, j: usize, rows: u32, columns: u32, wrap: bool) -> Vec<(usize, usize)> {
+        let mut res = Vec::new();
+        self.for_each_neighbour(i, j, rows, columns, wrap, |ni, nj| res.push((ni, nj)));
+        res
+    }
+
+    /// Calls `f` with every tile surrounding `(i, j)`, in the same order as `neighbours` lists them.
+    pub fn for_each_neighbour(&self, i: usize, j: usize, rows: u32, columns: u32, wrap: bool, mut f: impl FnMut(usize, usize)) {
+        let offsets: &[(i32, i32)] = match self {
+            Topology::Square => &[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
+            Topology::Hex if i.is_multiple_of(2) => &[(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)],
+            Topology::Hex => &[(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)],
+        };
+        // on very narrow wrapping boards different offsets can land on the same tile
+        let narrow = wrap && (rows < 3 || columns < 3);
+        let mut seen = [(i, j); 8];
+        let mut count = 0;
+        for (di, dj) in offsets {
+            let (mut ni, mut nj) = (i as i32 + di, j as i32 + dj);
+            if wrap {
+                ni = ni.rem_euclid(rows as i32);
+                nj = nj.rem_euclid(columns as i32);
+            } else if ni < 0 || nj < 0 || ni >= rows as i32 || nj >= columns as i32 {
                 continue;
             }
-            if i > 0 && minefield[i-1][j].is_bomb() {
-                count += 1;
-            }
-            if i < row_count as usize - 1  && minefield[i+1][j].is_bomb() {
-                count += 1;
-            }
-            if j > 0 && minefield[i][j-1].is_bomb() {
-                count += 1;
-            }
-            if j < col_count as usize - 1  && minefield[i][j+1].is_bomb() {
-                count += 1;
-            }
-            if i > 0 && j > 0 && minefield[i-1][j-1].is_bomb() {
+            let neighbour = (ni as usize, nj as usize);
+            if narrow {
+                if neighbour == (i, j) || seen[..count].contains(&neighbour) {
+                    continue;
+                }
+                seen[count] = neighbour;
                 count += 1;
             }
-            if i > 0 && j < col_count as usize - 1 && minefield[i-1][j+1].is_bomb() {
-                count += 1;
+            f(neighbour.0, neighbour.1);
+        }
+    }
+}
+
+/// Settings that change the shape of a board, as opposed to its size.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardOptions {
+    pub topology: Topology,
+    /// Whether the edges wrap around, so the board becomes a torus. Layers never wrap.
+    pub wrap: bool,
+    /// Number of stacked layers, more than one makes a three dimensional board.
+    pub layers: u32,
+    /// How many bombs a single tile can hold, flags are counted up to the same number.
+    pub bombs_per_tile: u32,
+}
+impl Default for BoardOptions {
+    fn default() -> Self {
+        Self {
+            topology: Topology::Square,
+            wrap: false,
+            layers: 1,
+            bombs_per_tile: 1,
+        }
+    }
+}
+impl BoardOptions {
+    pub fn max_bombs_per_tile(&self) -> u32 {
+        self.bombs_per_tile.max(1)
+    }
+
+    /// Indices of all tiles surrounding `(i, j)`, where the layers of `rows` rows each are stacked
+    /// below each other. A tile touches its neighbours on its own layer, as well as the tile right
+    /// above or below it and that one's neighbours on the adjacent layers.
+    pub fn neighbours(&self, i: usize, j: usize, rows: u32, columns: u32) -> Vec<(usize, usize)> {
+        let mut res = Vec::new();
+        self.for_each_neighbour(i, j, rows, columns, |ni, nj| res.push((ni, nj)));
+        res
+    }
+
+    /// Calls `f` with every tile surrounding `(i, j)`, in the same order as `neighbours` lists them.
+    pub fn for_each_neighbour(&self, i: usize, j: usize, rows: u32, columns: u32, mut f: impl FnMut(usize, usize)) {
+        let layer_rows = rows as usize;
+        let (layer, li) = (i / layer_rows, i % layer_rows);
+        let mut plane = [(0, 0); 8];
+        let mut count = 0;
+        self.topology.for_each_neighbour(li, j, rows, columns, self.wrap, |ni, nj| {
+            plane[count] = (ni, nj);
+            count += 1;
+        });
+        let plane = &plane[..count];
+        for (ni, nj) in plane {
+            f(layer * layer_rows + ni, *nj);
+        }
+        for other in [layer.wrapping_sub(1), layer + 1] {
+            if other >= self.layers.max(1) as usize {
+                continue;
             }
-            if i < row_count as usize - 1 && j > 0 && minefield[i+1][j-1].is_bomb() {
-                count += 1;
+            f(other * layer_rows + li, j);
+            for (ni, nj) in plane {
+                f(other * layer_rows + ni, *nj);
             }
-            if i < row_count as usize - 1 && j < col_count as usize - 1 && minefield[i+1][j+1].is_bomb() {
-                count += 1;
+        }
+    }
+}
+
+/// The bombs of a board and the numbers they add up to, without anything of a game played on it.
+/// Tiles are stored row by row, with the layers below each other.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Minefield {
+    /// Rows of all layers together.
+    rows: u32,
+    columns: u32,
+    mines: BitSet,
+    /// The bombs on a tile with bombs, and the bombs around it for every other tile.
+    values: Vec<u8>,
+}
+impl Minefield {
+    fn blank(rows: u32, columns: u32) -> Self {
+        let tiles = rows as usize * columns as usize;
+        Self {
+            rows,
+            columns,
+            mines: BitSet::new(tiles),
+            values: vec![0; tiles],
+        }
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    fn index(&self, i: usize, j: usize) -> usize {
+        i * self.columns as usize + j
+    }
+
+    pub fn value(&self, i: usize, j: usize) -> TileValue {
+        self.value_at(self.index(i, j))
+    }
+
+    fn value_at(&self, k: usize) -> TileValue {
+        match self.mines.get(k) {
+            true => TileValue::Bomb(self.values[k] as u32),
+            false => TileValue::Adjacent(self.values[k] as u32),
+        }
+    }
+
+    pub fn bombs(&self, i: usize, j: usize) -> u32 {
+        self.bombs_at(self.index(i, j))
+    }
+
+    fn bombs_at(&self, k: usize) -> u32 {
+        match self.mines.get(k) {
+            true => self.values[k] as u32,
+            false => 0,
+        }
+    }
+
+    /// Number of bombs on all tiles together.
+    pub fn bomb_total(&self) -> u32 {
+        self.mines.ones().map(|k| self.values[k] as u32).sum()
+    }
+
+    /// Puts one more bomb on a tile. The numbers around it are only right after `count_adjacent`.
+    fn add_bomb(&mut self, i: usize, j: usize) {
+        let k = self.index(i, j);
+        if self.mines.insert(k) {
+            self.values[k] = 0;
+        }
+        self.values[k] += 1;
+    }
+
+    /// Writes the number of surrounding bombs into every tile without a bomb.
+    fn count_adjacent(&mut self, layer_rows: u32, options: BoardOptions) {
+        for k in 0..self.values.len() {
+            if !self.mines.get(k) {
+                self.values[k] = 0;
             }
-            minefield[i][j].set_value(TileValue::Adjacent(count));
         }
+        let columns = self.columns as usize;
+        let mines: Vec<usize> = self.mines.ones().collect();
+        for k in mines {
+            let bombs = self.values[k];
+            options.for_each_neighbour(k / columns, k % columns, layer_rows, self.columns, |ni, nj| {
+                let n = ni * columns + nj;
+                if !self.mines.get(n) {
+                    self.values[n] += bombs;
+                }
+            });
+        }
+    }
+}
+
+/// Builds the tiles of a board with `row_count` rows per layer. Cells that are cut out by the `mask`
+/// and the cells in `exclude`, given with the rows of all layers counted together, never get a bomb.
+/// Fails when the bombs don't fit on the cells that are left.
+pub fn build_minefield(row_count: u32, col_count: u32, bomb_count: u32, options: BoardOptions, mask: Option<&Mask>, exclude: &[(usize, usize)]) -> Result<Minefield, EngineError> {
+    build_minefield_with(&mut rand::thread_rng(), row_count, col_count, bomb_count, options, mask, exclude)
+}
+
+/// Same as `build_minefield`, with the bombs placed by `rng`.
+pub fn build_minefield_with(rng: &mut impl Rng, row_count: u32, col_count: u32, bomb_count: u32, options: BoardOptions, mask: Option<&Mask>, exclude: &[(usize, usize)]) -> Result<Minefield, EngineError> {
+    let layer_rows = row_count;
+    let row_count = layer_rows * options.layers.max(1);
+    let mut minefield = Minefield::blank(row_count, col_count);
+    let columns = col_count as usize;
+    let playable = |k: usize| mask.is_none_or(|mask| mask.is_playable(k / columns % layer_rows as usize, k % columns));
+    let mut excluded = BitSet::new(minefield.values.len());
+    for (i, j) in exclude {
+        if *i >= row_count as usize || *j >= col_count as usize {
+            return Err(EngineError::OutsideBoard(*i, *j));
+        }
+        let k = minefield.index(*i, *j);
+        if !playable(k) {
+            return Err(EngineError::CutOut(*i, *j));
+        }
+        excluded.set(k, true);
+    }
+    // without holes every cell is free, and the list of free cells isn't needed
+    let free: Option<Vec<usize>> = match mask.is_some() || !exclude.is_empty() {
+        true => Some((0..excluded.len()).filter(|k| !excluded.get(*k) && playable(*k)).collect()),
+        false => None,
+    };
+    let free_count = free.as_ref().map_or(excluded.len(), |free| free.len());
+
+    // every free cell has a slot for each bomb it can hold, and the bombs go into distinct slots
+    // picked in one go, which takes as long for a full board as for an empty one
+    let per_tile = options.max_bombs_per_tile() as usize;
+    let slots = free_count * per_tile;
+    if bomb_count as usize > slots {
+        return Err(EngineError::TooManyBombs { bombs: bomb_count, free: free_count as u32 });
+    }
+    for slot in rand::seq::index::sample(rng, slots, bomb_count as usize) {
+        let k = free.as_ref().map_or(slot / per_tile, |free| free[slot / per_tile]);
+        minefield.add_bomb(k / columns, k % columns);
+    }
+    minefield.count_adjacent(layer_rows, options);
+    Ok(minefield)
+}
+
+/// Places the bombs with `(i, j)` free, and its neighbours as well as long as that leaves room
+/// for all bombs.
+fn safe_minefield(rng: &mut impl Rng, rows: u32, columns: u32, bombs: u32, options: BoardOptions, mask: Option<&Mask>, (i, j): (usize, usize)) -> Result<Minefield, EngineError> {
+    let mut exclude = vec![(i, j)];
+    options.for_each_neighbour(i, j, rows, columns, |ni, nj| {
+        // neighbours cut out by the mask never get a bomb anyway
+        if mask.is_none_or(|mask| mask.is_playable(ni % rows as usize, nj)) {
+            exclude.push((ni, nj));
+        }
+    });
+    match build_minefield_with(rng, rows, columns, bombs, options, mask, &exclude) {
+        Err(EngineError::TooManyBombs { .. }) => build_minefield_with(rng, rows, columns, bombs, options, mask, &[(i, j)]),
+        result => result,
     }
-    return minefield;
 }
 
-#[derive(Serialize, Deserialize)]
+/// A minefield and the progress of a game on it. The state of the tiles is kept in bit sets, so
+/// boards with a million tiles stay small and quick to flood fill.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Board {
-    minefield: Vec<Vec<Tile>>,
+    minefield: Minefield,
+    revealed: BitSet,
+    flagged: BitSet,
+    questioned: BitSet,
+    /// Flags on every tile, only kept when a tile can hold more than one bomb. Otherwise a tile
+    /// has a single flag when it is in `flagged`.
+    #[serde(default)]
+    flags: Vec<u8>,
+    /// Tiles without a bomb that are yet to be revealed, the game is won once there are none left.
+    hidden_safe: u32,
+    flag_total: u32,
+    /// Tiles a reveal has been through already, which is cleared again before the reveal returns.
+    #[serde(skip)]
+    checked: BitSet,
+    /// Rows of a single layer, the minefield holds all layers below each other.
     pub tile_rows: u32,
     pub tile_columns: u32,
 
     bomb_count: u32,
+    #[serde(default)]
+    options: BoardOptions,
+    /// Whether right clicks cycle through a question mark after the flag.
+    #[serde(default)]
+    question_marks: bool,
+    /// The bomb that ended the game, if one was triggered.
+    #[serde(default)]
+    exploded: Option<(usize, usize)>,
+    /// Cells outside of the mask are not part of the board.
+    #[serde(default)]
+    mask: Option<Mask>,
+    #[serde(default)]
+    state: GameState,
+    /// Where the bombs go when `protect_first_click` places them anew, so every copy of a board
+    /// places them the same way.
+    #[serde(default)]
+    seed: u64,
+    /// Whether the bombs were placed by hand with `with_bombs`, which keeps `protect_first_click`
+    /// from moving them.
+    #[serde(default)]
+    placed: bool,
 }
 
 impl Board {
-    pub fn new(rows: u32, columns: u32, bombs: u32) -> Self {
-        Self {
-            minefield: build_minefield(rows, columns, bombs),
+    pub fn new(rows: u32, columns: u32, bombs: u32) -> Result<Self, EngineError> {
+        Self::with_options(rows, columns, bombs, BoardOptions::default())
+    }
+
+    pub fn with_options(rows: u32, columns: u32, bombs: u32, options: BoardOptions) -> Result<Self, EngineError> {
+        Self::build(rows, columns, bombs, options, None)
+    }
+
+    /// Creates a board in the shape of `mask`, which also decides the number of rows and columns.
+    pub fn with_mask(bombs: u32, options: BoardOptions, mask: Mask) -> Result<Self, EngineError> {
+        Self::build(mask.rows(), mask.columns(), bombs, options, Some(mask))
+    }
+
+    /// Creates the same board every time for the same seed.
+    pub fn seeded(rows: u32, columns: u32, bombs: u32, options: BoardOptions, seed: u64) -> Result<Self, EngineError> {
+        let minefield = build_minefield_with(&mut StdRng::seed_from_u64(seed), rows, columns, bombs, options, None, &[])?;
+        let mut board = Self::from_minefield(minefield, rows, columns, bombs, options, None);
+        board.seed = seed;
+        Ok(board)
+    }
+
+    /// Creates a board where the tile `(i, j)` has no bomb, and neither have its neighbours as long
+    /// as that leaves room for all bombs, so a first click there always opens something up.
+    pub fn with_safe_tile(rows: u32, columns: u32, bombs: u32, options: BoardOptions, tile: (usize, usize)) -> Result<Self, EngineError> {
+        let minefield = safe_minefield(&mut rand::thread_rng(), rows, columns, bombs, options, None, tile)?;
+        Ok(Self::from_minefield(minefield, rows, columns, bombs, options, None))
+    }
+
+    /// Creates a board with bombs on exactly the given tiles, one more for every time a tile is
+    /// listed. Meant for puzzles and tests, where the board has to be known in advance.
+    pub fn with_bombs(rows: u32, columns: u32, bombs: &[(usize, usize)], options: BoardOptions) -> Self {
+        let mut minefield = Minefield::blank(rows * options.layers.max(1), columns);
+        for (i, j) in bombs {
+            minefield.add_bomb(*i, *j);
+        }
+        minefield.count_adjacent(rows, options);
+        let mut board = Self::from_minefield(minefield, rows, columns, bombs.len() as u32, options, None);
+        board.placed = true;
+        board
+    }
+
+    fn build(rows: u32, columns: u32, bombs: u32, options: BoardOptions, mask: Option<Mask>) -> Result<Self, EngineError> {
+        let minefield = build_minefield(rows, columns, bombs, options, mask.as_ref(), &[])?;
+        Ok(Self::from_minefield(minefield, rows, columns, bombs, options, mask))
+    }
+
+    fn from_minefield(minefield: Minefield, rows: u32, columns: u32, bombs: u32, options: BoardOptions, mask: Option<Mask>) -> Self {
+        let tiles = minefield.values.len();
+        let flags = match options.max_bombs_per_tile() {
+            1 => Vec::new(),
+            _ => vec![0; tiles],
+        };
+        let mut board = Self {
+            minefield,
+            revealed: BitSet::new(tiles),
+            flagged: BitSet::new(tiles),
+            questioned: BitSet::new(tiles),
+            flags,
+            hidden_safe: 0,
+            flag_total: 0,
+            checked: BitSet::new(tiles),
             tile_rows: rows,
             tile_columns: columns,
             bomb_count: bombs,
+            options,
+            question_marks: false,
+            exploded: None,
+            mask,
+            state: GameState::InGame,
+            seed: rand::random(),
+            placed: false,
+        };
+        board.hidden_safe = board.count_safe();
+        board
+    }
+
+    /// Number of playable tiles without a bomb.
+    fn count_safe(&self) -> u32 {
+        let columns = self.tile_columns as usize;
+        (0..self.minefield.values.len())
+            .filter(|k| !self.minefield.mines.get(*k) && self.is_playable(k / columns, k % columns))
+            .count() as u32
+    }
+
+    /// Creates a new board with the same size, bombs, options and shape.
+    pub fn restart(&self) -> Result<Self, EngineError> {
+        let mut board = Self::build(self.tile_rows, self.tile_columns, self.bomb_count, self.options(), self.mask.clone())?;
+        board.question_marks = self.question_marks;
+        Ok(board)
+    }
+
+    /// Gets the board ready for a first click on `(i, j)`. Until a tile has been revealed, the bombs
+    /// are placed anew so that `(i, j)` has none and its neighbours neither when there is room.
+    /// They follow from the board's seed, so the server and a client doing this on their own
+    /// copies end up with the same board. Flags stay where they are, and so do bombs placed with
+    /// `with_bombs`.
+    pub fn protect_first_click(&mut self, i: usize, j: usize) {
+        if self.placed || self.state != GameState::InGame || !self.is_playable(i, j) || self.revealed.count() > 0 {
+            return;
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        // a board that is all bombs has no room for a safe tile and stays as it is
+        if let Ok(minefield) = safe_minefield(&mut rng, self.tile_rows, self.tile_columns, self.bomb_count, self.options, self.mask.as_ref(), (i, j)) {
+            self.minefield = minefield;
+            self.hidden_safe = self.count_safe();
+        }
+    }
+
+    /// Whether `(i, j)` is part of the board, and not cut out by its mask.
+    pub fn is_playable(&self, i: usize, j: usize) -> bool {
+        i < (self.tile_rows * self.layers()) as usize
+            && j < self.tile_columns as usize
+            && self.mask.as_ref().is_none_or(|mask| mask.is_playable(i % self.tile_rows as usize, j))
+    }
+
+    fn index(&self, i: usize, j: usize) -> usize {
+        self.minefield.index(i, j)
+    }
+
+    fn flags_at(&self, k: usize) -> u32 {
+        match self.flags.is_empty() {
+            true => self.flagged.get(k) as u32,
+            false => self.flags[k] as u32,
+        }
+    }
+
+    fn set_flags(&mut self, k: usize, count: u32) {
+        self.flag_total = self.flag_total + count - self.flags_at(k);
+        self.flagged.set(k, count > 0);
+        if !self.flags.is_empty() {
+            self.flags[k] = count as u8;
+        }
+    }
+
+    fn state_at(&self, k: usize) -> TileState {
+        if self.revealed.get(k) {
+            TileState::Revealed
+        } else if self.flagged.get(k) {
+            TileState::Flagged(self.flags_at(k))
+        } else if self.questioned.get(k) {
+            TileState::Questioned
+        } else {
+            TileState::Hidden
         }
     }
 
-    fn reveal(&mut self, first_i: usize, first_j: usize, first_chain_reveal: bool) -> Result<(), String> {
+    fn reveal(&mut self, first_i: usize, first_j: usize, first_chain_reveal: bool) -> Reveal {
+        // grow the scratch space once, for boards that came over the network without it
+        if self.checked.len() != self.revealed.len() {
+            self.checked = BitSet::new(self.revealed.len());
+        }
+        let mut touched = Vec::new();
+        let revealed = self.flood(first_i, first_j, first_chain_reveal, &mut touched);
+        for k in touched {
+            self.checked.set(k, false);
+        }
+        revealed
+    }
+
+    fn flood(&mut self, first_i: usize, first_j: usize, first_chain_reveal: bool, touched: &mut Vec<usize>) -> Reveal {
+        // tiles are checked as they go onto the stack, so none of them is on it twice
+        let first = self.index(first_i, first_j);
+        self.checked.set(first, true);
+        touched.push(first);
         let mut to_reveal = vec![(first_i, first_j, first_chain_reveal)];
-        let mut checked = vec![vec![false; self.tile_columns as usize]; self.tile_rows as usize];
-        
-        while !to_reveal.is_empty() {
-            let (i,j, mut chain_reveal) = to_reveal.pop().unwrap();
-        
-            if checked[i][j] {
+
+        while let Some((i, j, mut chain_reveal)) = to_reveal.pop() {
+            let k = self.index(i, j);
+            if self.flagged.get(k) {
                 continue;
             }
-            checked[i][j] = true;
-            match self.minefield[i][j].state() {
-                TileState::Flagged => continue,
-                TileState::Revealed
-                | TileState::Hidden => {},
-            };
-            let flag_count = self.surrounding_flags(i, j);
-            match self.minefield[i][j].value() {
+            match self.minefield.value_at(k) {
                 TileValue::Adjacent(x) => {
-                    self.minefield[i][j].set_state(TileState::Revealed);
+                    if self.revealed.insert(k) {
+                        self.questioned.set(k, false);
+                        self.hidden_safe -= 1;
+                    }
                     if x == 0 {
                         chain_reveal = true;
                     }
-                    if flag_count != x {
-                        continue;
-                    }
-                    if !chain_reveal{
+                    if !chain_reveal || self.surrounding_flags(i, j) != x {
                         continue;
                     }
-                    if j > 0 {
-                        to_reveal.push((i, j-1, false));
-                    }
-                    if j < self.tile_columns as usize - 1 {
-                        to_reveal.push((i, j+1, false));
-                    }
-                    if i > 0 {
-                        to_reveal.push((i-1, j, false));
-                        if j > 0 {
-                            to_reveal.push((i-1, j-1, false));
-                        }
-                        if j < self.tile_columns as usize - 1 {
-                            to_reveal.push((i-1, j+1, false));
-                        }
-                    }
-                    if i < self.tile_rows as usize - 1 {
-                        to_reveal.push((i+1, j, false));
-                        if j > 0 {
-                            to_reveal.push((i+1, j-1, false));
-                        }
-                        if j < self.tile_columns as usize - 1 {
-                            to_reveal.push((i+1, j+1, false));
+                    let mut neighbours = [(0, 0); MAX_NEIGHBOURS];
+                    let mut count = 0;
+                    self.for_each_neighbour(i, j, |ni, nj| {
+                        neighbours[count] = (ni, nj);
+                        count += 1;
+                    });
+                    for (ni, nj) in &neighbours[..count] {
+                        let n = self.index(*ni, *nj);
+                        if self.checked.insert(n) {
+                            touched.push(n);
+                            to_reveal.push((*ni, *nj, false));
                         }
                     }
                 },
-                TileValue::Bomb => return Err("Bomb was triggered while revealing".to_string()),
+                TileValue::Bomb(_) => {
+                    self.exploded = Some((i, j));
+                    return Reveal::Exploded;
+                },
             }
         }
-        Ok(())
+        Reveal::Safe
     }
 
     fn surrounding_flags(&self, i: usize, j: usize) -> u32 {
+        if self.flag_total == 0 {
+            return 0;
+        }
         let mut count = 0;
-        if j > 0 {
-            if let TileState::Flagged = self.minefield[i][j-1].state() {
-                count += 1;
-            }
+        self.for_each_neighbour(i, j, |ni, nj| count += self.flags_at(self.index(ni, nj)));
+        count
+    }
+
+    /// Updates the game state after an attempt to reveal tiles.
+    fn update_state(&mut self, revealed: Reveal) {
+        self.state = match revealed {
+            Reveal::Exploded => GameState::GameOver,
+            Reveal::Safe => self.check_game_state(),
+        };
+    }
+
+    pub fn resolve_click(&mut self, i: usize, j: usize) {
+        if self.state != GameState::InGame || !self.is_playable(i, j) {
+            return;
         }
-        if j < self.tile_columns as usize - 1 {
-            if let TileState::Flagged = self.minefield[i][j+1].state() {
-                count += 1;
-            }
+        match self.state_at(self.index(i, j)) {
+            TileState::Hidden
+            | TileState::Questioned => {
+                let revealed = self.reveal(i, j, false);
+                self.update_state(revealed);
+            },
+            TileState::Revealed => {
+                let revealed = self.reveal(i, j, true);
+                self.update_state(revealed);
+            },
+            TileState::Flagged(_) => {},
+        };
+    }
+    
+    /// Reveals the hidden neighbours of a revealed tile, as with a middle or left+right click.
+    pub fn chord(&mut self, i: usize, j: usize, mismatch: ChordMismatch) {
+        if self.state != GameState::InGame || !self.is_playable(i, j) {
+            return;
         }
-        if i > 0 {
-            if let TileState::Flagged = self.minefield[i-1][j].state() {
-                count += 1;
-            }
-            if j > 0 {
-                if let TileState::Flagged = self.minefield[i-1][j-1].state() {
-                    count += 1;
-                }
-            }
-            if j < self.tile_columns as usize - 1 {
-                if let TileState::Flagged = self.minefield[i-1][j+1].state() {
-                    count += 1;
-                }
-            }
+        let x = match self.tile(i, j) {
+            Tile { state: TileState::Revealed, value: TileValue::Adjacent(x) } => x,
+            _ => return,
+        };
+        if self.surrounding_flags(i, j) == x {
+            let revealed = self.reveal(i, j, true);
+            self.update_state(revealed);
+            return;
         }
-        if i < self.tile_rows as usize - 1 {
-            if let TileState::Flagged = self.minefield[i+1][j].state() {
-                count += 1;
-            }
-            if j > 0 {
-                if let TileState::Flagged = self.minefield[i+1][j-1].state() {
-                    count += 1;
-            }
-            }
-            if j < self.tile_columns as usize - 1 {
-                if let TileState::Flagged = self.minefield[i+1][j+1].state() {
-                    count += 1;
+        match mismatch {
+            ChordMismatch::Ignore => {},
+            ChordMismatch::RevealUnflagged => {
+                for (ni, nj) in self.neighbours(i, j) {
+                    if let TileState::Hidden | TileState::Questioned = self.tile(ni, nj).state() {
+                        let revealed = self.reveal(ni, nj, false);
+                        self.update_state(revealed);
+                        if self.state == GameState::GameOver {
+                            return;
+                        }
+                    }
                 }
-            }
+            },
         }
-        return count;
     }
 
-    pub fn resolve_click(&mut self, game_state: &mut GameState, i: usize, j: usize) {
-        match self.minefield[i][j].state {
-            TileState::Hidden => {
-                match self.reveal(i, j, false) {
-                    Err(_) => {
-                        *game_state = GameState::GameOver;
-                    },
-                    Ok(_) => {},
-                };
-            },
-            TileState::Revealed => {
-                match self.reveal(i, j, true) {
-                    Err(_) => {
-                        *game_state = GameState::GameOver;
-                    },
-                    Ok(_) => {},
-                };
+    /// Indices of all tiles surrounding `(i, j)`.
+    pub fn neighbours(&self, i: usize, j: usize) -> Vec<(usize, usize)> {
+        let mut neighbours = Vec::new();
+        self.for_each_neighbour(i, j, |ni, nj| neighbours.push((ni, nj)));
+        neighbours
+    }
+
+    /// Calls `f` with every tile surrounding `(i, j)`, without collecting them first.
+    pub fn for_each_neighbour(&self, i: usize, j: usize, mut f: impl FnMut(usize, usize)) {
+        self.options.for_each_neighbour(i, j, self.tile_rows, self.tile_columns, |ni, nj| {
+            if self.is_playable(ni, nj) {
+                f(ni, nj);
+            }
+        });
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.options.topology
+    }
+
+    pub fn options(&self) -> BoardOptions {
+        self.options
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.options.layers.max(1)
+    }
+
+    /// Makes a move sent over the network, the same way on the server and on the client. The first
+    /// reveal is protected, and reveals chord already revealed tiles as `resolve_click` does;
+    /// `Won` and `Quit` leave the board alone.
+    pub fn apply(&mut self, action: Action) {
+        match action {
+            Action::Reveal(i, j) => {
+                self.protect_first_click(i as usize, j as usize);
+                self.resolve_click(i as usize, j as usize);
             },
-            TileState::Flagged => {}, 
-        };
+            Action::ToggleFlag(i, j) => self.resolve_flag(i as usize, j as usize),
+            Action::Won | Action::Quit => {},
+        }
     }
-    
+
     pub fn resolve_flag(&mut self, i: usize, j: usize) {
-        self.minefield[i][j].state = match self.minefield[i][j].state() {
-                TileState::Hidden => TileState::Flagged,
-                TileState::Revealed => TileState::Revealed,
-                TileState::Flagged => TileState::Hidden,
+        if self.state != GameState::InGame || !self.is_playable(i, j) {
+            return;
+        }
+        let k = self.index(i, j);
+        match self.state_at(k) {
+            TileState::Hidden => self.set_flags(k, 1),
+            TileState::Revealed => {},
+            TileState::Flagged(count) if count < self.options.max_bombs_per_tile() => self.set_flags(k, count + 1),
+            TileState::Flagged(_) if self.question_marks => {
+                self.set_flags(k, 0);
+                self.questioned.set(k, true);
+            },
+            TileState::Flagged(_) => self.set_flags(k, 0),
+            TileState::Questioned => self.questioned.set(k, false),
         };
     }
 
-    pub fn iter_field(&self) -> std::slice::Iter<Vec<Tile>> {
-        return self.minefield.iter()
+    pub fn exploded(&self) -> Option<(usize, usize)> {
+        self.exploded
+    }
+
+    pub fn set_question_marks(&mut self, enabled: bool) {
+        self.question_marks = enabled;
+    }
+
+    pub fn bomb_count(&self) -> u32 {
+        self.bomb_count
+    }
+
+    pub fn flag_count(&self) -> u32 {
+        self.flag_total
+    }
+
+    pub fn tile(&self, i: usize, j: usize) -> Tile {
+        let k = self.index(i, j);
+        Tile {
+            state: self.state_at(k),
+            value: self.minefield.value_at(k),
+        }
+    }
+
+    /// The tiles of row `i`, counting the rows of all layers together.
+    pub fn row(&self, i: usize) -> impl Iterator<Item = Tile> + '_ {
+        (0..self.tile_columns as usize).map(move |j| self.tile(i, j))
+    }
+
+    /// The tiles row by row, with the layers below each other.
+    pub fn iter_field(&self) -> impl Iterator<Item = impl Iterator<Item = Tile> + '_> + '_ {
+        (0..self.minefield.rows() as usize).map(move |i| self.row(i))
     }
 
+    /// The state the game is in, which only changes through clicks and chords.
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    /// Works out whether every tile without a bomb has been revealed. A lost game isn't detected here,
+    /// that is only known when the bomb goes off.
     pub fn check_game_state(&self) -> GameState {
-        for row in self.iter_field() {
-            for tile in row.iter() {
-                match (tile.value(), tile.state()) {
-                    (TileValue::Adjacent(_), TileState::Hidden)
-                    | (TileValue::Adjacent(_), TileState::Flagged) 
-                    => {
-                        return GameState::InGame
-                    },
-                    _ => {},
-                };
-            }
+        match self.hidden_safe {
+            0 => GameState::Won,
+            _ => GameState::InGame,
         }
-        GameState::Won
     }
 }
 
 pub fn clean_input(input: &str) -> String {
     let mut res = String::new();
     for c in input.chars() {
-        if c.is_digit(10) {
+        if c.is_ascii_digit() {
             res.push(c);
         }
     }
-    return res;
+    res
 }
 
-pub fn input_to_number(input: &str) -> u32 {
-    let mut res = 0;
+/// Reads the digits in `input` as a number. Returns `None` if there are no digits or the number doesn't fit into a `u32`.
+pub fn input_to_number(input: &str) -> Option<u32> {
+    let mut res: Option<u32> = None;
     for c in input.chars() {
-        if c.is_digit(10) {
-            res *= 10;
-            res += c.to_digit(10).unwrap();
+        if let Some(digit) = c.to_digit(10) {
+            res = Some(res.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+        }
+    }
+    res
+}
+
+pub const MIN_COLUMNS: u32 = 5;
+pub const MAX_COLUMNS: u32 = 200;
+pub const MIN_ROWS: u32 = 5;
+pub const MAX_ROWS: u32 = 200;
+/// Upper bound for the share of tiles that may be bombs, in percent.
+pub const MAX_BOMB_DENSITY: u32 = 85;
+pub const MAX_LAYERS: u32 = 9;
+pub const MAX_BOMBS_PER_TILE: u32 = 3;
+/// Most neighbours a tile can have, on a 3D board of square tiles.
+pub const MAX_NEIGHBOURS: usize = 26;
+/// Largest number a tile can show, when all 26 neighbours on a 3D board are full of bombs.
+pub const MAX_ADJACENT_BOMBS: u32 = MAX_NEIGHBOURS as u32 * MAX_BOMBS_PER_TILE;
+
+#[derive(Clone, Copy)]
+pub enum Preset {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Beginner, Preset::Intermediate, Preset::Expert];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Beginner => "Beginner",
+            Preset::Intermediate => "Intermediate",
+            Preset::Expert => "Expert",
         }
     }
-    return res;
-}
\ No newline at end of file
+    /// Returns `(columns, rows, bombs)` of the preset.
+    pub fn settings(&self) -> (u32, u32, u32) {
+        match self {
+            Preset::Beginner => (9, 9, 10),
+            Preset::Intermediate => (16, 16, 40),
+            Preset::Expert => (30, 16, 99),
+        }
+    }
+}
+
+pub fn check_size(value: Option<u32>, min: u32, max: u32) -> Result<u32, EngineError> {
+    match value {
+        Some(x) if x >= min && x <= max => Ok(x),
+        _ => Err(EngineError::OutOfRange { min, max }),
+    }
+}
+
+/// The most bombs that may be placed on a board with `tiles` playable tiles.
+pub fn max_bombs(tiles: u32) -> u32 {
+    (tiles * MAX_BOMB_DENSITY / 100).max(1)
+}
+
+pub fn check_bombs(value: Option<u32>, tiles: u32) -> Result<u32, EngineError> {
+    check_size(value, 1, max_bombs(tiles))
+}
+
+/// Checks the number of rows against the board options.
+pub fn check_rows(rows: u32, options: BoardOptions) -> Result<u32, EngineError> {
+    match (options.topology, options.wrap) {
+        // hex rows alternate their offset, so they only line up across the wrapped edge in pairs
+        (Topology::Hex, true) if rows % 2 == 1 => Err(EngineError::OddHexRows),
+        _ => Ok(rows),
+    }
+}
+
+/// Checks a complete set of board parameters, returning them as `(columns, rows, bombs)`.
+/// `rows` is the number of rows of a single layer.
+pub fn validate_settings(columns: Option<u32>, rows: Option<u32>, bombs: Option<u32>, options: BoardOptions) -> Result<(u32, u32, u32), EngineError> {
+    check_size(Some(options.layers), 1, MAX_LAYERS).map_err(EngineError::setting("layers"))?;
+    check_size(Some(options.bombs_per_tile), 1, MAX_BOMBS_PER_TILE).map_err(EngineError::setting("bombs per tile"))?;
+    let columns = check_size(columns, MIN_COLUMNS, MAX_COLUMNS).map_err(EngineError::setting("width"))?;
+    let rows = check_size(rows, MIN_ROWS, MAX_ROWS)
+        .and_then(|rows| check_rows(rows, options))
+        .map_err(EngineError::setting("height"))?;
+    let bombs = check_bombs(bombs, columns * rows * options.layers * options.bombs_per_tile).map_err(EngineError::setting("bombs"))?;
+    Ok((columns, rows, bombs))
+}
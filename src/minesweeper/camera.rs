@@ -0,0 +1,145 @@
+use sdl2::rect::{Point, Rect};
+
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 4.0;
+/// How much one step of the mouse wheel zooms in or out.
+pub const ZOOM_STEP: f32 = 1.25;
+
+/// Maps a world, the minefield laid out at its natural size, onto a part of the window.
+pub struct Camera {
+    /// World position shown in the top left corner of the viewport.
+    x: f32,
+    y: f32,
+    zoom: f32,
+    viewport: Rect,
+    world: (u32, u32),
+}
+
+impl Camera {
+    /// A camera showing the top left of a world of size `world` at its natural size in `viewport`.
+    pub fn new(viewport: Rect, world: (u32, u32)) -> Self {
+        let mut camera = Self { x: 0.0, y: 0.0, zoom: 1.0, viewport, world };
+        camera.clamp();
+        camera
+    }
+
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    /// Moves the view to another part of the window, keeping the zoom and position.
+    pub fn set_viewport(&mut self, viewport: Rect) {
+        self.viewport = viewport;
+        self.clamp();
+    }
+
+    pub fn world(&self) -> (u32, u32) {
+        self.world
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Whether part of the world is outside of the viewport.
+    pub fn can_pan(&self) -> bool {
+        self.world.0 as f32 * self.zoom > self.viewport.width() as f32
+            || self.world.1 as f32 * self.zoom > self.viewport.height() as f32
+    }
+
+    /// The part of the world that is visible, in world coordinates.
+    pub fn visible(&self) -> Rect {
+        Rect::new(
+            self.x.floor() as i32,
+            self.y.floor() as i32,
+            (self.viewport.width() as f32 / self.zoom).ceil() as u32,
+            (self.viewport.height() as f32 / self.zoom).ceil() as u32,
+        )
+    }
+
+    /// Where a world rectangle ends up in the window. Both edges are rounded the same way,
+    /// so neighbouring tiles never leave gaps between each other.
+    pub fn to_screen(&self, rect: Rect) -> Rect {
+        let left = self.screen_x(rect.left());
+        let top = self.screen_y(rect.top());
+        let right = self.screen_x(rect.right());
+        let bottom = self.screen_y(rect.bottom());
+        Rect::new(left, top, (right - left).max(1) as u32, (bottom - top).max(1) as u32)
+    }
+
+    fn screen_x(&self, x: i32) -> i32 {
+        self.viewport.x() + ((x as f32 - self.x) * self.zoom).floor() as i32
+    }
+
+    fn screen_y(&self, y: i32) -> i32 {
+        self.viewport.y() + ((y as f32 - self.y) * self.zoom).floor() as i32
+    }
+
+    /// The world position under a window position, if it is inside the viewport.
+    pub fn to_world(&self, point: Point) -> Option<Point> {
+        if !self.viewport.contains_point(point) {
+            return None;
+        }
+        Some(Point::new(
+            (self.x + (point.x() - self.viewport.x()) as f32 / self.zoom).floor() as i32,
+            (self.y + (point.y() - self.viewport.y()) as f32 / self.zoom).floor() as i32,
+        ))
+    }
+
+    /// Moves the view by a distance in window pixels.
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.x += dx as f32 / self.zoom;
+        self.y += dy as f32 / self.zoom;
+        self.clamp();
+    }
+
+    /// Zooms by `factor` while keeping the world position under the window position `anchor` in place.
+    pub fn zoom_at(&mut self, anchor: Point, factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let (ax, ay) = ((anchor.x() - self.viewport.x()) as f32, (anchor.y() - self.viewport.y()) as f32);
+        self.x += ax / self.zoom - ax / zoom;
+        self.y += ay / self.zoom - ay / zoom;
+        self.zoom = zoom;
+        self.clamp();
+    }
+
+    /// Centers the view on a world position.
+    pub fn center_on(&mut self, x: i32, y: i32) {
+        self.x = x as f32 - self.viewport.width() as f32 / self.zoom / 2.0;
+        self.y = y as f32 - self.viewport.height() as f32 / self.zoom / 2.0;
+        self.clamp();
+    }
+
+    /// Pans just far enough for a world rectangle to be fully visible.
+    pub fn show(&mut self, rect: Rect) {
+        let visible = self.visible();
+        if rect.left() < visible.left() {
+            self.x = rect.left() as f32;
+        } else if rect.right() > visible.right() {
+            self.x = rect.right() as f32 - self.viewport.width() as f32 / self.zoom;
+        }
+        if rect.top() < visible.top() {
+            self.y = rect.top() as f32;
+        } else if rect.bottom() > visible.bottom() {
+            self.y = rect.bottom() as f32 - self.viewport.height() as f32 / self.zoom;
+        }
+        self.clamp();
+    }
+
+    /// Keeps the world inside the view, centering it along the sides where it is smaller than the view.
+    fn clamp(&mut self) {
+        let (view_width, view_height) = (
+            self.viewport.width() as f32 / self.zoom,
+            self.viewport.height() as f32 / self.zoom,
+        );
+        let (world_width, world_height) = (self.world.0 as f32, self.world.1 as f32);
+        self.x = match world_width > view_width {
+            true => self.x.clamp(0.0, world_width - view_width),
+            false => (world_width - view_width) / 2.0,
+        };
+        self.y = match world_height > view_height {
+            true => self.y.clamp(0.0, world_height - view_height),
+            false => (world_height - view_height) / 2.0,
+        };
+    }
+}
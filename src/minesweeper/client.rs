@@ -1,21 +1,23 @@
-mod my_textures;
-mod board;
-
 use sdl2::{
-    image::LoadTexture,
     event::Event,
     keyboard::Keycode,
     mouse::MouseButton,
     pixels::Color,
-    rect::{Rect},
+    rect::{Rect, Point},
 };
-use std::net::UdpSocket;
-use crate::my_textures::*;
-use crate::board::{
+use std::time::Duration;
+use game_clones::app::{App, Assets, Flow, SceneStack, WindowSettings};
+use game_clones::minesweeper::my_textures::*;
+use game_clones::minesweeper::cursor::{Cursor, CursorAction};
+use game_clones::minesweeper::layout::Layout;
+use game_clones::minesweeper::error::{Error, ProtocolError};
+use game_clones::minesweeper::net::{Connection, SERVER_ADDRESS};
+use game_clones::minesweeper::board::{
+    self,
     TileState,
     TileValue,
-    Board,
     Action,
+    MAX_ADJACENT_BOMBS,
 };
 
 const TILE_ROWS: u32 = 9;
@@ -23,6 +25,9 @@ const TILE_COLUMNS: u32 = 9;
 const TILE_SIZE: u32 = 20;
 
 const BOMB_COUNT: u32 = 10;
+const WRAP_EDGES: bool = false;
+/// Nothing moves on its own, so a frame is just a chance to handle input.
+const FRAME: Duration = Duration::from_millis(16);
 
 enum GameState {
     Menu,
@@ -30,97 +35,43 @@ enum GameState {
     Won,
     GameOver,
 }
-
-fn validate_action(socket: &UdpSocket, action: Action) -> Result<bool, String> {
-    // send action
-    let message = serde_json::to_string(&action).map_err(|e| e.to_string())?;
-    socket.send(message.as_bytes()).map_err(|e| e.to_string())?;
-
-    // receive validity
-    let mut buf = [0; 50];
-    let amt = socket.recv(&mut buf).map_err(|e| e.to_string())?;
-    let mut tmp: Vec<u8> = Vec::from(buf);
-    tmp.resize(amt, 0);
-    let serialized = String::from_utf8(Vec::from(tmp)).map_err(|e| e.to_string())?;
-    let is_valid: bool = serde_json::from_str(&serialized).map_err(|e| e.to_string())?;
-    return Ok(is_valid);
+impl From<board::GameState> for GameState {
+    fn from(state: board::GameState) -> Self {
+        match state {
+            board::GameState::InGame => GameState::InGame,
+            board::GameState::Won => GameState::Won,
+            board::GameState::GameOver => GameState::GameOver,
+        }
+    }
 }
 
 fn main() -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-    
-    let mut window = video_subsystem
-        .window(
-            "SpaceInvaders",
-            TILE_COLUMNS * TILE_SIZE,
-            TILE_ROWS * TILE_SIZE,
-        )
-        .position_centered()
-        .build()
-        .map_err(|e| e.to_string())?;
-    window.set_bordered(false);
-
-    let mut canvas = window
-        .into_canvas()
-        .target_texture()
-        .present_vsync()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-    canvas.present();
-
-    
-    let mut texture_creator1 = canvas.texture_creator();
-    let (number_textures, surface_rect) = number_textures(&mut texture_creator1)?;
-
-    let texture_creator2 = canvas.texture_creator();
-    let flag_texture = texture_creator2
-        .load_texture("assets/flag_tile.png")
-        .map_err(|e| e.to_string())?;
-
-    let texture_creator3 = canvas.texture_creator();
-    let hidden_texture = texture_creator3
-        .load_texture("assets/hidden_tile.png")
-        .map_err(|e| e.to_string())?;
-
-    let texture_creator4 = canvas.texture_creator();
-    let revealed_texture = texture_creator4
-        .load_texture("assets/revealed_tile.png")
-        .map_err(|e| e.to_string())?;
+    let mut app = App::new(WindowSettings {
+        title: "Minesweeper",
+        width: TILE_COLUMNS * TILE_SIZE,
+        height: TILE_ROWS * TILE_SIZE,
+        resizable: false,
+    })?;
+    let assets = Assets::new(&app.canvas);
 
-    let mut texture_creator5 = canvas.texture_creator();
-    let (menu_texture, menu_rect) = text_texture(&mut texture_creator5, "> Start <", 24)?;
+    let (number_textures, surface_rect) = number_textures(assets.creator(), TILE_SIZE, MAX_ADJACENT_BOMBS, "assets/Monaco.ttf", |_| Color::BLACK)?;
+    let flag_texture = assets.texture("assets/flag_tile.png")?;
+    let hidden_texture = assets.texture("assets/hidden_tile.png")?;
+    let revealed_texture = assets.texture("assets/revealed_tile.png")?;
+    let question_texture = question_texture(&mut app.canvas, assets.creator(), TILE_SIZE, &hidden_texture)?;
+    let (menu_texture, menu_rect) = text_texture(assets.creator(), "> Start <", 24)?;
+    let (end_texture, end_rect) = text_texture(assets.creator(), "Game Over!", 24)?;
 
-    let mut texture_creator6 = canvas.texture_creator();
-    let (end_texture, end_rect) = text_texture(&mut texture_creator6, "Game Over!", 24)?;
-
-    // open socket
-    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
-    socket.connect("192.168.178.25:2024").map_err(|e| e.to_string())?;
-
-    // send parameters to server
-    let message: (u32,u32,u32,u32) = (TILE_SIZE, TILE_ROWS, TILE_COLUMNS, BOMB_COUNT);
-    let serialized = serde_json::to_string(&message).expect("could'nt serialize the board");
-    let amt = socket.send(serialized.as_bytes()).map_err(|e| e.to_string())?;
-    println!("size of message: {}", amt);
-
-    // receive board from server
-    let mut buf = [0; 27000];
-    let (amt, src) = socket.recv_from(&mut buf).map_err(|e| e.to_string())?;
-    let mut tmp: Vec<u8> = Vec::from(buf);
-    tmp.resize(amt, 0);
-    let serialized = String::from_utf8(Vec::from(tmp)).unwrap();
-    let mut board: Board = serde_json::from_str(&serialized).unwrap();
+    let (connection, mut board) = Connection::start(SERVER_ADDRESS, TILE_ROWS, TILE_COLUMNS, BOMB_COUNT, WRAP_EDGES)?;
+    let layout = Layout::new(&board, TILE_SIZE);
 
     let (mut pressed_i, mut pressed_j) = (None, None);
-    let mut game_state = GameState::Menu;
-    let mut event_pump = sdl_context.event_pump()?;
+    let mut cursor: Option<Cursor> = None;
+    let mut game_state = SceneStack::new(GameState::Menu);
 
-    'game_loop: loop {
-        match game_state {
+    app.run(FRAME, |app, _| {
+        let App { canvas, event_pump, .. } = app;
+        match *game_state {
             GameState::Menu => {
                 for event in event_pump.poll_iter() {
                     match event {
@@ -128,17 +79,14 @@ fn main() -> Result<(), String> {
                         | Event::KeyDown {
                                 keycode: Some(Keycode::Escape),
                                 ..
-                        } => match validate_action(&socket, Action::Quit)? {
-                            true => break 'game_loop,
-                            false => {
-                                println!("Not a valid action");
-                                continue;
-                            },
+                        } => {
+                            connection.quit()?;
+                            return Ok(Flow::Quit);
                         },
                         Event::KeyDown {
                                 keycode: Some(Keycode::Return),
                                 ..
-                        } => game_state = GameState::InGame,
+                        } => *game_state = GameState::InGame,
                         _ => {},
                     };
                 }
@@ -162,12 +110,30 @@ fn main() -> Result<(), String> {
                         | Event::KeyDown {
                             keycode: Some(Keycode::Escape),
                             ..
-                        } => match validate_action(&socket, Action::Quit)? {
-                            true => break 'game_loop,
-                            false => {
-                                println!("Not a valid action");
-                                continue;
-                            },
+                        } => {
+                            connection.quit()?;
+                            return Ok(Flow::Quit);
+                        },
+                        Event::KeyDown {
+                            keycode: Some(keycode),
+                            keymod,
+                            ..
+                        } => {
+                            let mut current = cursor.unwrap_or_else(|| Cursor::new(&board, 0));
+                            let (i, j) = (current.i, current.j);
+                            // a chord is a reveal of an already revealed tile to the server
+                            let action = match current.key(&board, keycode, keymod) {
+                                Some(CursorAction::Reveal)
+                                | Some(CursorAction::Chord) => Some(Action::Reveal(i as u32, j as u32)),
+                                Some(CursorAction::Flag) => Some(Action::ToggleFlag(i as u32, j as u32)),
+                                None if cursor.is_none() && (current.i, current.j) == (i, j) => continue,
+                                None => None,
+                            };
+                            cursor = Some(current);
+                            // a move the server turns down is left out on this side too
+                            if let Some(action) = action {
+                                connection.play(&mut board, action)?;
+                            }
                         },
                         Event::MouseButtonDown {
                             mouse_btn: MouseButton::Left,
@@ -189,13 +155,7 @@ fn main() -> Result<(), String> {
                             match (pressed_i, pressed_j) {
                                 (Some(i1), Some(j1)) => {
                                     if i1 == i && j1 == j {
-                                        match validate_action(&socket, Action::Reveal(i as u32,j as u32))? {
-                                            true => board.resolve_click(&mut game_state, i, j),
-                                            false => {
-                                                println!("Not a valid action");
-                                                continue;
-                                            },
-                                        };
+                                        connection.play(&mut board, Action::Reveal(i as u32, j as u32))?;
                                     }
                                 }
                                 _ => continue,
@@ -209,74 +169,71 @@ fn main() -> Result<(), String> {
                         } => {
                             let i = (y / TILE_SIZE as i32) as usize;
                             let j = (x / TILE_SIZE as i32) as usize;
-                            match validate_action(&socket, Action::ToggleFlag(i as u32,j as u32))? {
-                                true => board.resolve_flag(i, j),
-                                false => {
-                                    println!("Not a valid action");
-                                    continue;
-                                },
-                            };
-                            
+                            connection.play(&mut board, Action::ToggleFlag(i as u32, j as u32))?;
                         },
                         _ => {},
                     }
                 }
 
-                if let GameState::GameOver = game_state {
-                    continue;
-                }
-        
-                game_state = GameState::Won;
-                'check_won: for row in board.iter_field() {
-                    for tile in row.iter() {
-                        match (tile.value(), tile.state()) {
-                            (TileValue::Adjacent(_), TileState::Hidden)
-                            | (TileValue::Adjacent(_), TileState::Flagged) 
-                            => {
-                                game_state = GameState::InGame;
-                                break 'check_won;
-                            },
-                            _ => {},
-                        };
-                    }
-                }
+                // the end screens go on top of the game
+                match GameState::from(board.state()) {
+                    GameState::InGame => {},
+                    state => {
+                        game_state.push(state);
+                        return Ok(Flow::Continue);
+                    },
+                };
 
                 canvas.set_draw_color(Color::RGB(0, 0, 0));
                 canvas.clear();
-                for row in board.iter_field() {
-                    for tile in row.iter() {
+                for (i, row) in board.iter_field().enumerate() {
+                    for (j, tile) in row.enumerate() {
+                        if !board.is_playable(i, j) {
+                            continue;
+                        }
                         match tile.state() {
                             TileState::Revealed => {
                                 canvas.copy(
                                     &revealed_texture,
                                     None,
-                                    tile.rect(),
+                                    layout.tile_rect(Point::new(0, 0), i, j),
                                 )?;
                                 if let TileValue::Adjacent(x) = tile.value() {
                                     canvas.copy(
-                                        number_textures.get(x as usize).expect(format!("texture for index {x} doesnt exist").as_str()),
+                                        number_textures.get(x as usize).ok_or(format!("the server sent a tile with the number {x}, which has no texture"))?,
                                         None,
-                                        Rect::from_center(tile.center(), surface_rect.width(), surface_rect.height())
+                                        Rect::from_center(layout.tile_center(Point::new(0, 0), i, j), surface_rect.width(), surface_rect.height())
                                     )?;
                                 } 
                             },
-                            TileState::Flagged => {
+                            TileState::Flagged(_) => {
                                 canvas.copy(
                                     &flag_texture,
                                     None,
-                                    tile.rect(),
+                                    layout.tile_rect(Point::new(0, 0), i, j),
+                                )?;
+                            },
+                            TileState::Questioned => {
+                                canvas.copy(
+                                    &question_texture,
+                                    None,
+                                    layout.tile_rect(Point::new(0, 0), i, j),
                                 )?;
                             },
                             TileState::Hidden => {
                                 canvas.copy(
                                     &hidden_texture,
                                     None,
-                                    tile.rect(),
+                                    layout.tile_rect(Point::new(0, 0), i, j),
                                 )?;
                             },
                         }           
                     }
                 }
+                if let Some(cursor) = cursor {
+                    canvas.set_draw_color(Color::RGB(255, 220, 0));
+                    canvas.draw_rect(layout.tile_rect(Point::new(0, 0), cursor.i, cursor.j))?;
+                }
         
                 canvas.present();
             },
@@ -288,12 +245,9 @@ fn main() -> Result<(), String> {
                         | Event::KeyDown {
                                 keycode: Some(Keycode::Escape),
                                 ..
-                        } => match validate_action(&socket, Action::Quit)? {
-                            true => break 'game_loop,
-                            false => {
-                                println!("Not a valid action");
-                                continue;
-                            },
+                        } => {
+                            connection.quit()?;
+                            return Ok(Flow::Quit);
                         },
                         _ => {},
                     };
@@ -313,13 +267,13 @@ fn main() -> Result<(), String> {
 
             GameState::Won => {
                 println!("you've beaten the game :)");
-                match validate_action(&socket, Action::Won)? {
-                    true => break 'game_loop,
-                    false => panic!{"should'nt have won"},
-                };
+                // the server only takes the win when its own copy of the board was won as well
+                if !connection.validate(Action::Won)? {
+                    return Err(Error::from(ProtocolError::Rejected).into());
+                }
+                return Ok(Flow::Quit);
             },
         }
-    }
-
-    Ok(())
+        Ok(Flow::Continue)
+    })
 }
\ No newline at end of file
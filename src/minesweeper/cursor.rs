@@ -0,0 +1,101 @@
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::{Keycode, Mod};
+use crate::minesweeper::board::{Board, TileState};
+
+/// Number of tiles moved at once while ctrl is held.
+pub const PAGE_STEP: usize = 10;
+
+/// What a key asks to do with the tile under the cursor.
+pub enum CursorAction {
+    Reveal,
+    Flag,
+    Chord,
+}
+
+/// A tile picked with the keyboard, as row and column indices into the whole board.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub i: usize,
+    pub j: usize,
+}
+
+impl Cursor {
+    /// A cursor in the middle of the given layer.
+    pub fn new(board: &Board, layer: u32) -> Self {
+        Self {
+            i: (layer * board.tile_rows + board.tile_rows / 2) as usize,
+            j: (board.tile_columns / 2) as usize,
+        }
+    }
+
+    pub fn layer(&self, board: &Board) -> u32 {
+        self.i as u32 / board.tile_rows
+    }
+
+    /// Moves the cursor to the same row and column of another layer.
+    pub fn set_layer(&mut self, board: &Board, layer: u32) {
+        let rows = board.tile_rows as usize;
+        self.i = layer as usize * rows + self.i % rows;
+    }
+
+    /// Handles a key press, either moving the cursor or returning what to do with the tile under it.
+    ///
+    /// The arrows, WASD and hjkl move by one tile, or by a page while ctrl is held. Home and End go to
+    /// the ends of the row, and Tab jumps to the next hidden tile bordering a revealed one, or the
+    /// previous one with shift. Space and Return reveal, F flags and C chords.
+    #[cfg(feature = "sdl")]
+    pub fn key(&mut self, board: &Board, keycode: Keycode, keymod: Mod) -> Option<CursorAction> {
+        let step = match keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+            true => PAGE_STEP as i64,
+            false => 1,
+        };
+        match keycode {
+            Keycode::Left | Keycode::A | Keycode::H => self.shift(board, 0, -step),
+            Keycode::Right | Keycode::D | Keycode::L => self.shift(board, 0, step),
+            Keycode::Up | Keycode::W | Keycode::K => self.shift(board, -step, 0),
+            Keycode::Down | Keycode::S | Keycode::J => self.shift(board, step, 0),
+            Keycode::Home => self.j = 0,
+            Keycode::End => self.j = board.tile_columns as usize - 1,
+            Keycode::Tab => self.next_edge(board, !keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)),
+            Keycode::Space | Keycode::Return => return Some(CursorAction::Reveal),
+            Keycode::F => return Some(CursorAction::Flag),
+            Keycode::C => return Some(CursorAction::Chord),
+            _ => {},
+        };
+        None
+    }
+
+    /// Moves by `(di, dj)` tiles, stopping at the edges of the current layer.
+    pub fn shift(&mut self, board: &Board, di: i64, dj: i64) {
+        let rows = board.tile_rows as i64;
+        let layer_start = self.i as i64 / rows * rows;
+        let row = (self.i as i64 - layer_start + di).clamp(0, rows - 1);
+        self.i = (layer_start + row) as usize;
+        self.j = (self.j as i64 + dj).clamp(0, board.tile_columns as i64 - 1) as usize;
+    }
+
+    /// Jumps to the next hidden tile next to a revealed one, going through the board row by row
+    /// and wrapping around at the end. Stays put when there is no such tile.
+    pub fn next_edge(&mut self, board: &Board, forward: bool) {
+        let columns = board.tile_columns as usize;
+        let tiles = board.tile_rows as usize * board.layers() as usize * columns;
+        let current = self.i * columns + self.j;
+        for step in 1..tiles {
+            let index = match forward {
+                true => (current + step) % tiles,
+                false => (current + tiles - step) % tiles,
+            };
+            let (i, j) = (index / columns, index % columns);
+            if board.is_playable(i, j) && is_edge(board, i, j) {
+                (self.i, self.j) = (i, j);
+                return;
+            }
+        }
+    }
+}
+
+/// Whether `(i, j)` is still hidden while one of its neighbours has been revealed.
+fn is_edge(board: &Board, i: usize, j: usize) -> bool {
+    matches!(board.tile(i, j).state(), TileState::Hidden | TileState::Questioned)
+        && board.neighbours(i, j).iter().any(|(ni, nj)| matches!(board.tile(*ni, *nj).state(), TileState::Revealed))
+}
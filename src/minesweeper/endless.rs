@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use crate::minesweeper::bits::BitSet;
+use crate::minesweeper::board::{GameState, Reveal, TileState, check_size, MAX_BOMB_DENSITY};
+use crate::minesweeper::error::EngineError;
+
+/// Width and height of a chunk, in tiles.
+pub const CHUNK_SIZE: i64 = 16;
+/// Lowest bomb density of an endless field. Below this the empty areas get so large that
+/// a single click can flood fill a huge part of the field.
+pub const MIN_ENDLESS_DENSITY: u32 = 12;
+/// Chunks further away than this many chunks from the camera are packed away.
+pub const EVICT_DISTANCE: i64 = 4;
+const CHUNK_TILES: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// The state of one square of tiles. Bombs aren't stored, they follow from the seed.
+struct Chunk {
+    states: Vec<TileState>,
+    /// Number of tiles in the chunk without a bomb.
+    safe: u32,
+    revealed: u32,
+}
+impl Chunk {
+    fn is_cleared(&self) -> bool {
+        self.revealed == self.safe
+    }
+
+    /// Cuts the chunk down to what sets it apart from a fresh one, or to nothing when that is nothing.
+    fn pack(&self) -> Option<Packed> {
+        let mut revealed = BitSet::new(CHUNK_TILES);
+        let mut flagged = BitSet::new(CHUNK_TILES);
+        for (index, state) in self.states.iter().enumerate() {
+            match state {
+                TileState::Revealed => revealed.set(index, true),
+                TileState::Flagged(_) => flagged.set(index, true),
+                _ => {},
+            }
+        }
+        let flagged = (flagged.count() > 0).then_some(flagged);
+        if self.revealed == 0 && flagged.is_none() {
+            return None;
+        }
+        Some(Packed {
+            revealed: (!self.is_cleared()).then_some(revealed),
+            flagged,
+        })
+    }
+}
+
+/// A chunk out of memory. Its revealed and flagged tiles are kept as bits, except for the revealed
+/// tiles of a cleared chunk, which are all of its safe tiles and follow from the seed.
+struct Packed {
+    /// `None` when the chunk was cleared.
+    revealed: Option<BitSet>,
+    /// `None` when nothing was flagged.
+    flagged: Option<BitSet>,
+}
+impl Packed {
+    fn state(&self, index: usize, bomb: bool) -> TileState {
+        if self.flagged.as_ref().is_some_and(|flagged| flagged.get(index)) {
+            return TileState::Flagged(1);
+        }
+        let revealed = match &self.revealed {
+            Some(revealed) => revealed.get(index),
+            None => !bomb,
+        };
+        match revealed {
+            true => TileState::Revealed,
+            false => TileState::Hidden,
+        }
+    }
+}
+
+/// A minefield without edges. Tiles are addressed by `(x, y)` and chunks are only allocated
+/// once something in them changes. Chunks away from the camera are packed down to bits of their
+/// revealed and flagged tiles, and a cleared chunk without flags to little more than its
+/// coordinates, so only the area around the camera is held in full.
+pub struct EndlessBoard {
+    seed: u64,
+    density: u32,
+    chunks: HashMap<(i64, i64), Chunk>,
+    packed: HashMap<(i64, i64), Packed>,
+    revealed: u64,
+    exploded: Option<(i64, i64)>,
+}
+
+impl EndlessBoard {
+    /// Creates a field where roughly `density` percent of the tiles are bombs. The tiles around
+    /// the origin are always safe, so revealing `(0, 0)` is a good way to start.
+    pub fn new(seed: u64, density: u32) -> Self {
+        Self {
+            seed,
+            density,
+            chunks: HashMap::new(),
+            packed: HashMap::new(),
+            revealed: 0,
+            exploded: None,
+        }
+    }
+
+    /// A new field with the same density and a different seed.
+    pub fn restart(&self) -> Self {
+        Self::new(rand::random(), self.density)
+    }
+
+    pub fn is_bomb(&self, x: i64, y: i64) -> bool {
+        if x.abs() <= 1 && y.abs() <= 1 {
+            return false;
+        }
+        // splitmix64 of the seed and position, so every tile can be generated on its own
+        let mut z = self.seed
+            ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z % 100) < self.density as u64
+    }
+
+    /// Number of bombs surrounding `(x, y)`.
+    pub fn adjacent(&self, x: i64, y: i64) -> u32 {
+        neighbours(x, y).filter(|(nx, ny)| self.is_bomb(*nx, *ny)).count() as u32
+    }
+
+    pub fn state(&self, x: i64, y: i64) -> TileState {
+        let (key, index) = chunk_index(x, y);
+        match self.chunks.get(&key) {
+            Some(chunk) => chunk.states[index],
+            None => match self.packed.get(&key) {
+                Some(packed) => packed.state(index, self.is_bomb(x, y)),
+                None => TileState::Hidden,
+            },
+        }
+    }
+
+    /// Brings a chunk back into memory, unpacking it if it had been evicted.
+    fn chunk_mut(&mut self, key: (i64, i64)) -> &mut Chunk {
+        if !self.chunks.contains_key(&key) {
+            let packed = self.packed.remove(&key);
+            let mut states = Vec::with_capacity(CHUNK_TILES);
+            let (mut safe, mut revealed) = (0, 0);
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let bomb = self.is_bomb(key.0 * CHUNK_SIZE + x, key.1 * CHUNK_SIZE + y);
+                    if !bomb {
+                        safe += 1;
+                    }
+                    let state = packed.as_ref().map_or(TileState::Hidden, |packed| packed.state(states.len(), bomb));
+                    if state == TileState::Revealed {
+                        revealed += 1;
+                    }
+                    states.push(state);
+                }
+            }
+            self.chunks.insert(key, Chunk { states, safe, revealed });
+        }
+        self.chunks.get_mut(&key).unwrap()
+    }
+
+    fn set_state(&mut self, x: i64, y: i64, state: TileState) {
+        let (key, index) = chunk_index(x, y);
+        let chunk = self.chunk_mut(key);
+        let old = std::mem::replace(&mut chunk.states[index], state);
+        if let (TileState::Hidden | TileState::Questioned, TileState::Revealed) = (old, state) {
+            chunk.revealed += 1;
+            self.revealed += 1;
+        }
+    }
+
+    /// Reveals a tile and floods outwards from empty tiles.
+    fn reveal(&mut self, x: i64, y: i64) -> Reveal {
+        let mut to_visit = vec![(x, y)];
+        while let Some((x, y)) = to_visit.pop() {
+            match self.state(x, y) {
+                TileState::Hidden | TileState::Questioned => {},
+                _ => continue,
+            };
+            if self.is_bomb(x, y) {
+                self.exploded = Some((x, y));
+                return Reveal::Exploded;
+            }
+            self.set_state(x, y, TileState::Revealed);
+            if self.adjacent(x, y) == 0 {
+                to_visit.extend(neighbours(x, y));
+            }
+        }
+        Reveal::Safe
+    }
+
+    pub fn resolve_click(&mut self, x: i64, y: i64) {
+        if self.exploded.is_some() {
+            return;
+        }
+        match self.state(x, y) {
+            TileState::Revealed => self.chord(x, y),
+            TileState::Flagged(_) => {},
+            _ => {
+                self.reveal(x, y);
+            },
+        };
+    }
+
+    /// Reveals the hidden neighbours of a revealed tile whose bombs have all been flagged.
+    pub fn chord(&mut self, x: i64, y: i64) {
+        if self.exploded.is_some() || !matches!(self.state(x, y), TileState::Revealed) {
+            return;
+        }
+        let flags = neighbours(x, y).filter(|(nx, ny)| matches!(self.state(*nx, *ny), TileState::Flagged(_))).count() as u32;
+        if flags != self.adjacent(x, y) {
+            return;
+        }
+        for (nx, ny) in neighbours(x, y) {
+            if self.reveal(nx, ny) == Reveal::Exploded {
+                return;
+            }
+        }
+    }
+
+    pub fn resolve_flag(&mut self, x: i64, y: i64) {
+        match self.state(x, y) {
+            TileState::Hidden | TileState::Questioned => self.set_state(x, y, TileState::Flagged(1)),
+            TileState::Flagged(_) => self.set_state(x, y, TileState::Hidden),
+            TileState::Revealed => {},
+        };
+    }
+
+    /// Packs away the chunks that are more than `EVICT_DISTANCE` chunks away from the tile `(x, y)`,
+    /// however far they have been cleared.
+    pub fn evict(&mut self, x: i64, y: i64) {
+        let (center, _) = chunk_index(x, y);
+        let far: Vec<(i64, i64)> = self.chunks
+            .keys()
+            .filter(|key| (key.0 - center.0).abs().max((key.1 - center.1).abs()) > EVICT_DISTANCE)
+            .copied()
+            .collect();
+        for key in far {
+            if let Some(packed) = self.chunks.remove(&key).and_then(|chunk| chunk.pack()) {
+                self.packed.insert(key, packed);
+            }
+        }
+    }
+
+    /// Number of chunks held in full, which `evict` keeps down to the ones around the camera.
+    pub fn loaded_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Number of tiles revealed so far, which is the score of an endless game.
+    pub fn score(&self) -> u64 {
+        self.revealed
+    }
+
+    pub fn exploded(&self) -> Option<(i64, i64)> {
+        self.exploded
+    }
+
+    /// An endless game can't be won, it goes on until a bomb is hit.
+    pub fn game_state(&self) -> GameState {
+        match self.exploded {
+            Some(_) => GameState::GameOver,
+            None => GameState::InGame,
+        }
+    }
+}
+
+/// The eight tiles surrounding `(x, y)`.
+pub fn neighbours(x: i64, y: i64) -> impl Iterator<Item = (i64, i64)> {
+    (-1..=1)
+        .flat_map(move |dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| (*dx, *dy) != (0, 0))
+        .map(move |(dx, dy)| (x + dx, y + dy))
+}
+
+/// The chunk holding the tile `(x, y)` and the tile's index within it.
+fn chunk_index(x: i64, y: i64) -> ((i64, i64), usize) {
+    let key = (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE));
+    let index = y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + x.rem_euclid(CHUNK_SIZE);
+    (key, index as usize)
+}
+
+/// Checks the bomb density of an endless field, in percent.
+pub fn check_density(value: Option<u32>) -> Result<u32, EngineError> {
+    check_size(value, MIN_ENDLESS_DENSITY, MAX_BOMB_DENSITY)
+}
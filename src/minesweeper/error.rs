@@ -0,0 +1,161 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Anything that keeps minesweeper from going on. Hitting a bomb isn't one of them, that is how
+/// a game ends and shows up in `GameState`.
+#[derive(Debug)]
+pub enum Error {
+    Protocol(ProtocolError),
+    Io(io::Error),
+    Asset(AssetError),
+    Engine(EngineError),
+}
+
+/// A message that doesn't follow the protocol between client and server.
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// Bytes that aren't UTF-8.
+    NotUtf8,
+    /// Text that isn't the JSON of the message that was expected.
+    Malformed(serde_json::Error),
+    /// A message too long for a single datagram.
+    TooLong,
+    /// The server turned the request down.
+    Rejected,
+    /// The server didn't answer in time, or isn't there at all.
+    NoAnswer,
+}
+
+/// A file of the game that couldn't be loaded.
+#[derive(Debug)]
+pub enum AssetError {
+    /// The file couldn't be read at all.
+    Read(PathBuf, io::Error),
+    /// A theme that isn't valid JSON or leaves something out.
+    Theme(String),
+    /// A mask without a single playable cell.
+    EmptyMask,
+    /// SDL couldn't load an image, font or texture.
+    Sdl(String),
+}
+
+/// Board settings or requests the engine can't build a board from.
+#[derive(Debug, PartialEq)]
+pub enum EngineError {
+    /// A value outside of the range it has to be in.
+    OutOfRange { min: u32, max: u32 },
+    /// An odd number of rows on a wrapping hex grid, whose rows only line up in pairs.
+    OddHexRows,
+    /// The named setting failed the check.
+    Setting(&'static str, Box<EngineError>),
+    /// More bombs than fit on the tiles that may hold them.
+    TooManyBombs { bombs: u32, free: u32 },
+    /// A tile that isn't on the board.
+    OutsideBoard(usize, usize),
+    /// A tile the mask cuts out of the board.
+    CutOut(usize, usize),
+}
+
+impl EngineError {
+    /// Turns an error into one about the named setting, for use with `map_err`.
+    pub fn setting(name: &'static str) -> impl Fn(EngineError) -> EngineError {
+        move |e| EngineError::Setting(name, Box::new(e))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Protocol(e) => write!(f, "protocol error: {e}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Asset(e) => write!(f, "{e}"),
+            Error::Engine(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::NotUtf8 => write!(f, "message is not UTF-8"),
+            ProtocolError::Malformed(e) => write!(f, "malformed message: {e}"),
+            ProtocolError::TooLong => write!(f, "message doesn't fit in a datagram"),
+            ProtocolError::Rejected => write!(f, "the server turned the request down"),
+            ProtocolError::NoAnswer => write!(f, "the server didn't answer"),
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Read(path, e) => write!(f, "{}: {e}", path.display()),
+            AssetError::Theme(message) => write!(f, "{message}"),
+            AssetError::EmptyMask => write!(f, "mask has no playable cells"),
+            AssetError::Sdl(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::OutOfRange { min, max } => write!(f, "must be between {min} and {max}"),
+            EngineError::OddHexRows => write!(f, "must be even for a wrapping hex grid"),
+            EngineError::Setting(name, e) => write!(f, "{name} {e}"),
+            EngineError::TooManyBombs { bombs, free } => write!(f, "{bombs} bombs don't fit on {free} free tiles"),
+            EngineError::OutsideBoard(i, j) => write!(f, "tile ({i}, {j}) is outside of the board"),
+            EngineError::CutOut(i, j) => write!(f, "tile ({i}, {j}) is cut out by the mask"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+impl std::error::Error for ProtocolError {}
+impl std::error::Error for AssetError {}
+impl std::error::Error for EngineError {}
+
+impl From<ProtocolError> for Error {
+    fn from(e: ProtocolError) -> Self {
+        Error::Protocol(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<AssetError> for Error {
+    fn from(e: AssetError) -> Self {
+        Error::Asset(e)
+    }
+}
+
+impl From<EngineError> for Error {
+    fn from(e: EngineError) -> Self {
+        Error::Engine(e)
+    }
+}
+
+// SDL reports its errors as text, so the front-ends that use it pass everything on as text too
+
+impl From<Error> for String {
+    fn from(e: Error) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<AssetError> for String {
+    fn from(e: AssetError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<EngineError> for String {
+    fn from(e: EngineError) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,105 @@
+use sdl2::rect::{Point, Rect};
+use crate::minesweeper::board::{Board, Topology};
+
+/// Where the tiles of a board are drawn. The board only knows its tiles by their indices,
+/// every pixel position is worked out here.
+#[derive(Clone, Copy)]
+pub struct Layout {
+    topology: Topology,
+    /// Rows of a single layer, every layer is drawn at the same place.
+    rows: u32,
+    columns: u32,
+    tile_size: u32,
+}
+
+impl Layout {
+    pub fn new(board: &Board, tile_size: u32) -> Self {
+        Self {
+            topology: board.topology(),
+            rows: board.tile_rows,
+            columns: board.tile_columns,
+            tile_size,
+        }
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Pixel position of the top left corner of tile `(i, j)`'s bounding box.
+    pub fn tile_position(&self, i: usize, j: usize) -> (i32, i32) {
+        let (i, j, size) = (i as u32 % self.rows, j as u32, self.tile_size);
+        match self.topology {
+            Topology::Square => ((j * size) as i32, (i * size) as i32),
+            Topology::Hex => ((j * size + (i % 2) * size / 2) as i32, (i * size * 3 / 4) as i32),
+        }
+    }
+
+    pub fn tile_rect(&self, origin: Point, i: usize, j: usize) -> Rect {
+        let (x, y) = self.tile_position(i, j);
+        Rect::new(origin.x() + x, origin.y() + y, self.tile_size, self.tile_size)
+    }
+
+    pub fn tile_center(&self, origin: Point, i: usize, j: usize) -> Point {
+        self.tile_rect(origin, i, j).center()
+    }
+
+    /// Size in pixels of a single layer of the minefield.
+    pub fn field_size(&self) -> (u32, u32) {
+        let size = self.tile_size;
+        match self.topology {
+            Topology::Square => (self.columns * size, self.rows * size),
+            Topology::Hex => (self.columns * size + size / 2, (self.rows - 1) * size * 3 / 4 + size),
+        }
+    }
+
+    /// Returns the indices of the tile of `board` under the pixel `(x, y)`, with the given layer drawn starting at `origin`.
+    pub fn tile_at(&self, board: &Board, origin: Point, layer: u32, x: i32, y: i32) -> Option<(usize, usize)> {
+        let (x, y) = (x - origin.x(), y - origin.y());
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let row_height = match self.topology {
+            Topology::Square => self.tile_size,
+            Topology::Hex => self.tile_size * 3 / 4,
+        } as i32;
+        // hexagons overlap the row above, so the tile may also belong to that one
+        let last_row = (y / row_height) as u32;
+        for i in (last_row.saturating_sub(1)..=last_row).rev() {
+            if i >= self.rows {
+                continue;
+            }
+            let (row_x, _) = self.tile_position(i as usize, 0);
+            if x < row_x {
+                continue;
+            }
+            let j = ((x - row_x) / self.tile_size as i32) as usize;
+            if j >= self.columns as usize {
+                continue;
+            }
+            let (tile_x, tile_y) = self.tile_position(i as usize, j);
+            let i = (layer * self.rows + i) as usize;
+            if tile_contains(self.topology, x - tile_x, y - tile_y, self.tile_size) && board.is_playable(i, j) {
+                return Some((i, j));
+            }
+        }
+        None
+    }
+}
+
+/// Whether the point `(x, y)`, relative to the top left corner of a tile, lies on that tile.
+pub fn tile_contains(topology: Topology, x: i32, y: i32, tile_size: u32) -> bool {
+    let size = tile_size as i32;
+    if x < 0 || y < 0 || x >= size || y >= size {
+        return false;
+    }
+    match topology {
+        Topology::Square => true,
+        Topology::Hex => {
+            // the slanted edges span the top and bottom quarter of the tile, measured at pixel centers
+            let quarter = size / 4;
+            let from_edge = y.min(size - 1 - y);
+            from_edge >= quarter || (2 * from_edge + 1) * (size / 2) >= quarter * (2 * (x - size / 2) + 1).abs()
+        },
+    }
+}
@@ -1,92 +1,389 @@
-mod my_textures;
-mod board;
-
 use sdl2::{
-    image::LoadTexture,
-    event::Event,
-    keyboard::Keycode,
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
     mouse::MouseButton,
     pixels::Color,
     rect::{Rect, Point},
+    render::BlendMode,
+};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
 };
-use crate::my_textures::*;
-use crate::board::{
+use game_clones::app::{App, Assets, Flow, SceneStack, WindowSettings, asset_path};
+use game_clones::minesweeper::my_textures::*;
+use game_clones::minesweeper::mask::Mask;
+use game_clones::minesweeper::layout::Layout;
+use game_clones::minesweeper::render::*;
+use game_clones::minesweeper::endless::{self, EndlessBoard, check_density};
+use game_clones::minesweeper::camera::{Camera, ZOOM_STEP};
+use game_clones::minesweeper::cursor::{Cursor, CursorAction};
+use game_clones::minesweeper::theme::{builtin_themes, user_themes};
+use game_clones::minesweeper::error::{AssetError, EngineError};
+use game_clones::minesweeper::board::{
+    self,
     clean_input,
     input_to_number,
-    TileState,
-    TileValue,
+    check_size,
+    check_bombs,
+    Preset,
+    MIN_COLUMNS,
+    MAX_COLUMNS,
+    MIN_ROWS,
+    MAX_ROWS,
+    MAX_LAYERS,
+    MAX_BOMBS_PER_TILE,
     Board,
+    ChordMismatch,
+    BoardOptions,
+    check_rows,
 };
 
-const MENU_HEIGHT: u32 = 320;
+const MENU_HEIGHT: u32 = 420;
 const MENU_WIDTH: u32 = 600;
 const TILE_SIZE: u32 = 20;
+/// Limits of the tile size setting, in pixels.
+const MIN_TILE_SIZE: u32 = 8;
+const MAX_TILE_SIZE: u32 = 128;
+/// Largest area below the HUD the window grows to, larger boards are seen through the camera.
+const MAX_VIEW_WIDTH: u32 = 1000;
+const MAX_VIEW_HEIGHT: u32 = 700;
+/// The game only reacts to input, so a frame is just a chance to handle events and redraw.
+const FRAME: Duration = Duration::from_millis(16);
+/// How far the mouse has to move with the left button held before it pans instead of clicking.
+const DRAG_THRESHOLD: i32 = 5;
 
 enum GameState {
     Menu,
     InGame,
+    /// Playing on an endless field, which can only be lost.
+    Endless,
     Won,
     GameOver,
 }
+impl From<board::GameState> for GameState {
+    fn from(state: board::GameState) -> Self {
+        match state {
+            board::GameState::InGame => GameState::InGame,
+            board::GameState::Won => GameState::Won,
+            board::GameState::GameOver => GameState::GameOver,
+        }
+    }
+}
+
+/// A camera for the board, with a viewport below the HUD no larger than the maximum view size.
+fn board_camera(board: &Board, layout: &Layout) -> Camera {
+    let (width, height) = view_size(board, layout);
+    let viewport = Rect::new(0, HUD_HEIGHT as i32, width.min(MAX_VIEW_WIDTH), height.min(MAX_VIEW_HEIGHT));
+    Camera::new(viewport, (width, height))
+}
+
+/// Fits the board into a resized window. While the whole board is on screen at its natural size
+/// the tiles grow or shrink with the window, otherwise only the viewport changes.
+/// Returns whether the tile size changed, in which case the textures have to be drawn again.
+fn resize_board(board: &Board, layout: &mut Layout, camera: &mut Camera, width: u32, height: u32) -> bool {
+    let height = height.saturating_sub(HUD_HEIGHT).max(1);
+    let viewport = Rect::new(0, HUD_HEIGHT as i32, width.max(1), height);
+    if camera.zoom() != 1.0 || camera.can_pan() {
+        camera.set_viewport(viewport);
+        return false;
+    }
+    let (view_width, view_height) = camera.world();
+    let tile_size = (layout.tile_size() * width / view_width)
+        .min(layout.tile_size() * height / view_height)
+        .clamp(MIN_TILE_SIZE, MAX_TILE_SIZE);
+    let changed = tile_size != layout.tile_size();
+    *layout = Layout::new(board, tile_size);
+    *camera = Camera::new(viewport, view_size(board, layout));
+    changed
+}
+
+/// Fits the view of an endless field into a resized window, keeping the middle of the view in place.
+fn resize_endless(view: &mut (u32, u32), camera: &mut (i64, i64), width: u32, height: u32) {
+    let resized = (width.max(1), height.saturating_sub(HUD_HEIGHT).max(1));
+    camera.0 += (view.0 as i64 - resized.0 as i64) / 2;
+    camera.1 += (view.1 as i64 - resized.1 as i64) / 2;
+    *view = resized;
+}
+
+/// The tile of the current layer under the window position `(x, y)`.
+fn tile_under(board: &Board, layout: &Layout, camera: &Camera, layer: u32, x: i32, y: i32) -> Option<(usize, usize)> {
+    let world = camera.to_world(Point::new(x, y))?;
+    layout.tile_at(board, board_origin(board, layout), layer, world.x(), world.y())
+}
+
+/// The world position shown at a window position on the minimap.
+fn minimap_to_world(camera: &Camera, minimap: Rect, point: Point) -> Point {
+    let (world_width, world_height) = camera.world();
+    Point::new(
+        (point.x() - minimap.x()) * world_width as i32 / minimap.width() as i32,
+        (point.y() - minimap.y()) * world_height as i32 / minimap.height() as i32,
+    )
+}
+
+/// Zooms with the mouse wheel around the mouse position and pans with shift and the arrow keys,
+/// leaving the plain arrows to the keyboard cursor. Returns whether the event was used by the camera.
+fn camera_event(camera: &mut Camera, event: &Event, mouse: Point) -> bool {
+    let step = TILE_SIZE as i32 * 2;
+    let shift = |keymod: &Mod| keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+    match event {
+        Event::MouseWheel { y, .. } if *y != 0 => camera.zoom_at(mouse, ZOOM_STEP.powi(*y)),
+        Event::KeyDown { keycode: Some(Keycode::Left), keymod, .. } if shift(keymod) => camera.pan(-step, 0),
+        Event::KeyDown { keycode: Some(Keycode::Right), keymod, .. } if shift(keymod) => camera.pan(step, 0),
+        Event::KeyDown { keycode: Some(Keycode::Up), keymod, .. } if shift(keymod) => camera.pan(0, -step),
+        Event::KeyDown { keycode: Some(Keycode::Down), keymod, .. } if shift(keymod) => camera.pan(0, step),
+        _ => return false,
+    };
+    true
+}
+
+/// What a mouse button does to a game with tiles of type `T`.
+enum Click<T> {
+    Reveal(T),
+    Flag(T),
+    Chord(T),
+    Face,
+}
+
+/// The mouse buttons held over a game, which turn into clicks, flags and chords the same way on the
+/// board and in endless mode. The tile that is drawn pressed down is kept by the caller.
+#[derive(Default)]
+struct Buttons {
+    left: bool,
+    right: bool,
+    middle: bool,
+    chording: bool,
+    /// Set when a chord was released while another button is still held, so that release doesn't click.
+    chord_consumed: bool,
+    face_pressed: bool,
+}
+impl Buttons {
+    /// Presses `button` with `tile` under the mouse. Only the right button does something at once.
+    fn press<T: Copy>(&mut self, button: MouseButton, tile: Option<T>, pressed: &mut Option<T>, on_face: bool) -> Option<Click<T>> {
+        match button {
+            MouseButton::Left => self.left = true,
+            MouseButton::Right => self.right = true,
+            MouseButton::Middle => self.middle = true,
+            _ => return None,
+        };
+        *pressed = tile;
+        if self.middle || (self.left && self.right) {
+            self.chording = true;
+            self.face_pressed = false;
+            return None;
+        }
+        match button {
+            MouseButton::Left => {
+                self.face_pressed = on_face;
+                None
+            },
+            MouseButton::Right => tile.map(Click::Flag),
+            _ => None,
+        }
+    }
+
+    /// Follows the mouse onto `tile` while a click or chord is held.
+    fn hover<T>(&self, tile: Option<T>, pressed: &mut Option<T>) {
+        if self.left || self.chording {
+            *pressed = tile;
+        }
+    }
+
+    /// Releases `button` over `tile`. A release that ends a drag of the camera does nothing else.
+    fn release<T: Copy + PartialEq>(&mut self, button: MouseButton, tile: Option<T>, pressed: &mut Option<T>, on_face: bool, dragged: bool) -> Option<Click<T>> {
+        match button {
+            MouseButton::Left => self.left = false,
+            MouseButton::Right => self.right = false,
+            MouseButton::Middle => self.middle = false,
+            _ => return None,
+        };
+        if dragged {
+            *pressed = None;
+            return None;
+        }
+        if self.chording {
+            self.chording = false;
+            self.chord_consumed = self.left || self.right || self.middle;
+            *pressed = None;
+            return tile.map(Click::Chord);
+        }
+        let mut click = None;
+        if let MouseButton::Left = button {
+            if self.chord_consumed {
+                // the other half of a finished chord
+            } else if self.face_pressed && on_face {
+                click = Some(Click::Face);
+            } else if tile.is_some() && tile == *pressed {
+                click = tile.map(Click::Reveal);
+            }
+            self.face_pressed = false;
+            *pressed = None;
+        }
+        if !self.left && !self.right && !self.middle {
+            self.chord_consumed = false;
+        }
+        click
+    }
+}
+
+/// Panning by dragging with the left button, or by holding it down on the minimap.
+#[derive(Default)]
+struct Drag {
+    start: Option<Point>,
+    panning: bool,
+    minimap: bool,
+}
+impl Drag {
+    /// Starts a drag at `point`. Returns true when the press landed on the minimap and is used up by it.
+    fn press(&mut self, camera: &mut Camera, point: Point) -> bool {
+        if let Some(minimap) = minimap_rect(camera).filter(|minimap| minimap.contains_point(point)) {
+            let world = minimap_to_world(camera, minimap, point);
+            camera.center_on(world.x(), world.y());
+            self.minimap = true;
+            return true;
+        }
+        self.start = Some(point);
+        false
+    }
+
+    /// Follows the mouse while dragging. Panning only starts once the mouse has moved far enough
+    /// from where the button went down. Returns true while the camera is being moved.
+    fn motion(&mut self, camera: &mut Camera, point: Point, xrel: i32, yrel: i32, allow_pan: bool) -> bool {
+        if self.minimap {
+            if let Some(minimap) = minimap_rect(camera) {
+                let world = minimap_to_world(camera, minimap, point);
+                camera.center_on(world.x(), world.y());
+            }
+            return true;
+        }
+        let moved = self.start.is_some_and(|start| {
+            (point.x() - start.x()).abs().max((point.y() - start.y()).abs()) > DRAG_THRESHOLD
+        });
+        if allow_pan && camera.can_pan() && (self.panning || moved) {
+            self.panning = true;
+            camera.pan(-xrel, -yrel);
+        }
+        self.panning
+    }
+
+    /// Ends the drag, returning true if the press was used for moving the camera rather than a click.
+    fn release(&mut self) -> bool {
+        let used = self.panning || self.minimap;
+        *self = Drag::default();
+        used
+    }
+}
+
+/// Bomb density in percent of an endless field, taken from the bombs per tile of the board size in the menu.
+fn endless_density(columns: u32, rows: u32, bombs: u32) -> Result<u32, String> {
+    check_density(Some(bombs * 100 / (columns * rows))).map_err(|e| format!("bomb density {e}%"))
+}
 
+/// Validates the width, height, bomb, layer, bombs per tile and tile size text boxes of the menu,
+/// in that order. With a mask, its size is used instead of the width and height boxes.
+fn menu_settings(boxes: &[(Rect, String)], options: BoardOptions, mask: Option<&Mask>) -> [Result<u32, EngineError>; 6] {
+    let layers = check_size(input_to_number(&boxes[3].1), 1, MAX_LAYERS);
+    let bombs_per_tile = check_size(input_to_number(&boxes[4].1), 1, MAX_BOMBS_PER_TILE);
+    let (columns, rows) = match mask {
+        Some(mask) => (Some(mask.columns()), Some(mask.rows())),
+        None => (input_to_number(&boxes[0].1), input_to_number(&boxes[1].1)),
+    };
+    let columns = check_size(columns, MIN_COLUMNS, MAX_COLUMNS);
+    let rows = check_size(rows, MIN_ROWS, MAX_ROWS)
+        .and_then(|rows| check_rows(rows, options));
+    let bombs = match (&columns, &rows, &layers, &bombs_per_tile) {
+        (Ok(columns), Ok(rows), Ok(layers), Ok(bombs_per_tile)) => {
+            let tiles = mask.map_or(columns * rows, |mask| mask.playable_count());
+            check_bombs(input_to_number(&boxes[2].1), tiles * layers * bombs_per_tile)
+        },
+        // the bomb limit depends on the board size, so there is nothing to check against yet
+        _ => Ok(0),
+    };
+    let tile_size = check_size(input_to_number(&boxes[5].1), MIN_TILE_SIZE, MAX_TILE_SIZE);
+    [columns, rows, bombs, layers, bombs_per_tile, tile_size]
+}
+
+/// The tile of an endless field under the window position `(x, y)`, where `camera` is the
+/// field position in pixels shown in the top left corner below the HUD.
+fn endless_tile_at(camera: (i64, i64), tile_size: u32, x: i32, y: i32) -> Option<(i64, i64)> {
+    if y < HUD_HEIGHT as i32 {
+        return None;
+    }
+    let tile_size = tile_size as i64;
+    Some(((camera.0 + x as i64).div_euclid(tile_size), (camera.1 + (y - HUD_HEIGHT as i32) as i64).div_euclid(tile_size)))
+}
 
 fn main() -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-    let text_subsystem = video_subsystem.text_input();
-    
-    let mut window = video_subsystem
-        .window(
-            "Minesweeper",
-            MENU_WIDTH,
-            MENU_HEIGHT,
-        )
-        .position_centered()
-        .resizable()
-        .build()
-        .map_err(|e| e.to_string())?;
-    window.set_bordered(false);
-
-    let mut canvas = window
-        .into_canvas()
-        .target_texture()
-        .present_vsync()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-    canvas.present();
-
-    let mut texture_creator1 = canvas.texture_creator();
-    let (number_textures, surface_rect) = number_textures(&mut texture_creator1)?;
-
-    let texture_creator2 = canvas.texture_creator();
-    let flag_texture = texture_creator2
-        .load_texture("assets/flag_tile.png")
-        .map_err(|e| e.to_string())?;
-
-    let texture_creator3 = canvas.texture_creator();
-    let hidden_texture = texture_creator3
-        .load_texture("assets/hidden_tile.png")
-        .map_err(|e| e.to_string())?;
-
-    let texture_creator4 = canvas.texture_creator();
-    let revealed_texture = texture_creator4
-        .load_texture("assets/revealed_tile.png")
-        .map_err(|e| e.to_string())?;
-
-    let mut texture_creator5 = canvas.texture_creator();
-    let (menu_texture, menu_rect) = text_texture(&mut texture_creator5, "> Start <", 24)?;
-
-    let mut texture_creator6 = canvas.texture_creator();
-    let (game_over_texture, game_over_rect) = text_texture(&mut texture_creator6, "Game Over!", 24)?;
-
-    let mut texture_creator7 = canvas.texture_creator();
-    let (won_texture, won_rect) = text_texture(&mut texture_creator7, "You have won :)", 24)?;
+    let mut app = App::new(WindowSettings {
+        title: "Minesweeper",
+        width: MENU_WIDTH,
+        height: MENU_HEIGHT,
+        resizable: true,
+    })?;
+    let text_subsystem = app.video.text_input();
+
+    // everything is drawn onto textures from the same creator, so switching themes just replaces them
+    let assets = Assets::new(&app.canvas);
+    let mut themes = builtin_themes();
+    for theme in user_themes() {
+        match theme {
+            Ok(theme) => themes.push(theme),
+            Err(e) => eprintln!("skipping theme {e}"),
+        };
+    }
+    let mut theme_index = 0;
+    let mut theme_error: Option<String> = None;
+    // tiles start out at the same physical size on every display, 96 dpi being the usual desktop density
+    let mut tile_size = app.video
+        .display_dpi(0)
+        .map_or(TILE_SIZE, |(_, hdpi, _)| (TILE_SIZE as f32 * hdpi / 96.0).round() as u32)
+        .clamp(TILE_SIZE, MAX_TILE_SIZE);
+    let mut textures = board_textures(&mut app.canvas, assets.creator(), &themes[theme_index], tile_size)?;
+
+    let (menu_texture, menu_rect) = text_texture(assets.creator(), "> Start <", 24)?;
+    let (game_over_texture, game_over_rect) = text_texture(assets.creator(), "Game Over!", 24)?;
+    let (won_texture, won_rect) = text_texture(assets.creator(), "You have won :)", 24)?;
     
-    let (mut pressed_i, mut pressed_j) = (None, None);
-    let mut board = Board::new(5, 5, 1);
+    let mut pressed_tile: Option<(usize, usize)> = None;
+    let mut buttons = Buttons::default();
+    let mut chord_mismatch = ChordMismatch::Ignore;
+    let chord_toggle_rect = Rect::new(5, 130, 300, 20);
+    let mut question_marks = false;
+    let question_toggle_rect = Rect::new(5, 150, 300, 20);
+    let mut options = BoardOptions::default();
+    let topology_toggle_rect = Rect::new(5, 170, 300, 20);
+    let wrap_toggle_rect = Rect::new(5, 190, 300, 20);
+
+    // masks can be given on the command line, dropped onto the menu or picked from the assets
+    let mut mask_paths: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    let mut selected_mask: Option<(usize, Result<Mask, AssetError>)> = mask_paths
+        .first()
+        .map(|path| (0, load_mask(path)));
+    if let Ok(entries) = std::fs::read_dir(asset_path("assets/masks")) {
+        let mut bundled: Vec<PathBuf> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+        bundled.sort();
+        mask_paths.append(&mut bundled);
+    }
+    let mask_toggle_rect = Rect::new(5, 210, 300, 20);
+    let mut endless_mode = false;
+    let mode_toggle_rect = Rect::new(5, 230, 300, 20);
+    let theme_toggle_rect = Rect::new(5, 250, 300, 20);
+    let mut endless: Option<EndlessBoard> = None;
+    // field position in pixels of the top left corner of the view, and the size of the view
+    let mut endless_camera: (i64, i64) = (0, 0);
+    let mut endless_view: (u32, u32) = (0, 0);
+    let mut endless_pressed: Option<(i64, i64)> = None;
+    let mut start_time: Option<Instant> = None;
+    let mut end_time: Option<Instant> = None;
+    let mut board = Board::new(5, 5, 1)?;
+    let mut layout = Layout::new(&board, tile_size);
+    let mut camera = board_camera(&board, &layout);
+    // only shown once a key has moved it, so mouse players never see it
+    let mut cursor: Option<Cursor> = None;
+    let mut drag = Drag::default();
+    // the mouse wheel doesn't say where the mouse is, so remember it for zooming
+    let mut mouse = Point::new(0, 0);
+    let mut layer: u32 = 0;
     let (mut end_texture, mut end_rect) = (&game_over_texture, &game_over_rect);
 
     // initialize textbox
@@ -94,14 +391,23 @@ fn main() -> Result<(), String> {
         (Rect::new(5, 5, 100, 20), "Width: 30".to_string()),
         (Rect::new(5, 25, 100, 20), "Height: 16".to_string()),
         (Rect::new(5, 45, 100, 20), "Bombs: 99".to_string()),
+        (Rect::new(5, 65, 100, 20), "Layers: 1".to_string()),
+        (Rect::new(5, 85, 100, 20), "Bombs per tile: 1".to_string()),
+        (Rect::new(5, 105, 100, 20), format!("Tile size: {tile_size}")),
     ];
     let mut to_edit: Option<usize> = None;
+    let preset_rects: Vec<(Rect, Preset)> = Preset::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, preset)| (Rect::new(5 + 160 * i as i32, 280, 150, 20), *preset))
+        .collect();
 
-    let mut game_state = GameState::Menu;
-    let mut event_pump = sdl_context.event_pump()?;
+    // the end screens go on top of the game they ended
+    let mut game_state = SceneStack::new(GameState::Menu);
 
-    'game_loop: loop {
-        match game_state {
+    app.run(FRAME, |app, _| {
+        let App { canvas, event_pump, .. } = app;
+        match *game_state {
             GameState::Menu => {
                 for event in event_pump.poll_iter() {
                     match event {
@@ -109,18 +415,61 @@ fn main() -> Result<(), String> {
                         | Event::KeyDown {
                                 keycode: Some(Keycode::Escape),
                                 ..
-                        } => break 'game_loop,
+                        } => return Ok(Flow::Quit),
                         Event::KeyDown {
                                 keycode: Some(Keycode::Return),
                                 ..
                         } => {
-                            game_state = GameState::InGame;
+                            let mask = selected_mask.as_ref().and_then(|(_, mask)| mask.as_ref().ok()).filter(|_| !endless_mode);
+                            let settings = match menu_settings(&boxes, options, mask) {
+                                [Ok(columns), Ok(rows), Ok(bombs), Ok(layers), Ok(bombs_per_tile), Ok(size)] => [columns, rows, bombs, layers, bombs_per_tile, size],
+                                _ => continue,
+                            };
+                            if settings[5] != tile_size {
+                                tile_size = settings[5];
+                                textures = board_textures(canvas, assets.creator(), &themes[theme_index], tile_size)?;
+                            }
+                            if endless_mode {
+                                // the menu's board size becomes the size of the view, centered on the safe start
+                                let density = match endless_density(settings[0], settings[1], settings[2]) {
+                                    Ok(density) => density,
+                                    Err(_) => continue,
+                                };
+                                let mut field = EndlessBoard::new(rand::random(), density);
+                                field.resolve_click(0, 0);
+                                endless = Some(field);
+                                endless_view = (settings[0] * tile_size, settings[1] * tile_size);
+                                endless_camera = (-((endless_view.0 - tile_size) as i64) / 2, -((endless_view.1 - tile_size) as i64) / 2);
+                                *game_state = GameState::Endless;
+                                text_subsystem.stop();
+                                start_time = Some(Instant::now());
+                                end_time = None;
+                                canvas
+                                    .window_mut()
+                                    .set_size(hud_width(endless_view.0), endless_view.1 + HUD_HEIGHT)
+                                    .map_err(|e| e.to_string())?;
+                                continue;
+                            }
+                            endless = None;
+                            options.layers = settings[3];
+                            options.bombs_per_tile = settings[4];
+                            board = match &selected_mask {
+                                Some((_, Ok(mask))) => Board::with_mask(settings[2], options, mask.clone())?,
+                                Some((_, Err(_))) => continue,
+                                None => Board::with_options(settings[1], settings[0], settings[2], options)?,
+                            };
+                            *game_state = GameState::InGame;
                             text_subsystem.stop();
-                            let settings: Vec<u32> = boxes.iter().map(|(_,text)| input_to_number(text)).collect();
-                            board = Board::new(settings[1], settings[0], settings[2]);
+                            board.set_question_marks(question_marks);
+                            layer = 0;
+                            start_time = None;
+                            end_time = None;
+                            layout = Layout::new(&board, tile_size);
+                            camera = board_camera(&board, &layout);
+                            cursor = None;
                             canvas
                                 .window_mut()
-                                .set_size(settings[0] * TILE_SIZE, settings[1] * TILE_SIZE)
+                                .set_size(hud_width(camera.viewport().width()), camera.viewport().height() + HUD_HEIGHT)
                                 .map_err(|e| e.to_string())?;
                         },
                         Event::MouseButtonDown {
@@ -137,17 +486,67 @@ fn main() -> Result<(), String> {
                                     to_edit = Some(i);
                                 }
                             }
-                            if let None = to_edit {
+                            if to_edit.is_none() {
                                 text_subsystem.stop();
                             }
+                            if chord_toggle_rect.contains_point(Point::new(x, y)) {
+                                chord_mismatch = chord_mismatch.next();
+                            }
+                            if question_toggle_rect.contains_point(Point::new(x, y)) {
+                                question_marks = !question_marks;
+                            }
+                            if topology_toggle_rect.contains_point(Point::new(x, y)) {
+                                options.topology = options.topology.next();
+                            }
+                            if wrap_toggle_rect.contains_point(Point::new(x, y)) {
+                                options.wrap = !options.wrap;
+                            }
+                            if mode_toggle_rect.contains_point(Point::new(x, y)) {
+                                endless_mode = !endless_mode;
+                            }
+                            if theme_toggle_rect.contains_point(Point::new(x, y)) {
+                                theme_index = (theme_index + 1) % themes.len();
+                                // a theme whose images or font can't be loaded keeps the current textures
+                                match board_textures(canvas, assets.creator(), &themes[theme_index], tile_size) {
+                                    Ok(theme_textures) => {
+                                        textures = theme_textures;
+                                        theme_error = None;
+                                    },
+                                    Err(e) => theme_error = Some(e),
+                                };
+                            }
+                            if mask_toggle_rect.contains_point(Point::new(x, y)) {
+                                let next = match &selected_mask {
+                                    None => 0,
+                                    Some((i, _)) => i + 1,
+                                };
+                                selected_mask = mask_paths.get(next).map(|path| (next, load_mask(path)));
+                            }
+                            for (rect, preset) in preset_rects.iter() {
+                                if rect.contains_point(Point::new(x, y)) {
+                                    let (columns, rows, bombs) = preset.settings();
+                                    boxes[0].1 = format!("Width: {columns}");
+                                    boxes[1].1 = format!("Height: {rows}");
+                                    boxes[2].1 = format!("Bombs: {bombs}");
+                                }
+                            }
+                        },
+                        Event::DropFile {
+                            filename,
+                            ..
+                        } => {
+                            mask_paths.push(PathBuf::from(filename));
+                            let i = mask_paths.len() - 1;
+                            selected_mask = Some((i, load_mask(&mask_paths[i])));
                         },
                         Event::TextInput {
                             text,
                             ..
                         } => {
-                            let cleaned = clean_input(&text);
-                            let (_rect, input) = boxes.get_mut(to_edit.unwrap()).unwrap();
-                            input.push_str(&cleaned);
+                            if let Some(i) = to_edit {
+                                let cleaned = clean_input(&text);
+                                boxes[i].1.push_str(&cleaned);
+                            }
                         },
                         Event::KeyDown {
                             keycode: Some(Keycode::Backspace),
@@ -155,7 +554,7 @@ fn main() -> Result<(), String> {
                         } => { 
                             if let Some(i) = to_edit {
                                 let last = boxes.get_mut(i).unwrap().1.pop().unwrap();
-                                if !last.is_digit(10) {
+                                if !last.is_ascii_digit() {
                                     boxes.get_mut(i).unwrap().1.push(last);
                                 }
                             }
@@ -166,17 +565,93 @@ fn main() -> Result<(), String> {
                 canvas.set_draw_color(Color::RGB(50, 50, 50));
                 canvas.clear();
         
-                let center = Rect::new(0, 0, MENU_WIDTH, MENU_HEIGHT).center();
+                let center = Rect::new(0, (MENU_HEIGHT / 2) as i32, MENU_WIDTH, MENU_HEIGHT / 2).center();
                 canvas.copy(
                     &menu_texture,
                     None,
                     Rect::from_center(center, menu_rect.width(), menu_rect.height()),
                 )?;
 
-                // render user text
-                for (rect, text) in boxes.iter() {
-                    render_text(&mut canvas, rect.x(), rect.y(), text.as_str())?;
-                } 
+                // render user text with the reason next to it, if the value can't be used
+                let mask = selected_mask.as_ref().and_then(|(_, mask)| mask.as_ref().ok()).filter(|_| !endless_mode);
+                let checks = menu_settings(&boxes, options, mask);
+                let density = match &checks {
+                    [Ok(columns), Ok(rows), Ok(bombs), ..] if endless_mode => endless_density(*columns, *rows, *bombs).err(),
+                    _ => None,
+                };
+                for (i, ((rect, text), check)) in boxes.iter().zip(checks).enumerate() {
+                    match mask {
+                        Some(mask) if i < 2 => {
+                            let size = if i == 0 { mask.columns() } else { mask.rows() };
+                            let label = text.split(':').next().unwrap_or_default();
+                            render_text(canvas, rect.x(), rect.y(), format!("{label}: {size} (mask)").as_str())?;
+                        },
+                        _ => {
+                            render_text(canvas, rect.x(), rect.y(), text.as_str())?;
+                        },
+                    };
+                    if let Err(e) = check {
+                        render_colored_text(canvas, rect.right() + 20, rect.y(), e.to_string().as_str(), Color::RGB(230, 80, 80))?;
+                    }
+                }
+                for (rect, preset) in preset_rects.iter() {
+                    render_text(canvas, rect.x(), rect.y(), format!("[{}]", preset.name()).as_str())?;
+                }
+                render_text(
+                    canvas,
+                    chord_toggle_rect.x(),
+                    chord_toggle_rect.y(),
+                    format!("Chord mismatch: {}", chord_mismatch.name()).as_str(),
+                )?;
+                render_text(
+                    canvas,
+                    question_toggle_rect.x(),
+                    question_toggle_rect.y(),
+                    format!("Question marks: {}", if question_marks { "on" } else { "off" }).as_str(),
+                )?;
+                render_text(
+                    canvas,
+                    topology_toggle_rect.x(),
+                    topology_toggle_rect.y(),
+                    format!("Grid: {}", options.topology.name()).as_str(),
+                )?;
+                render_text(
+                    canvas,
+                    wrap_toggle_rect.x(),
+                    wrap_toggle_rect.y(),
+                    format!("Edges: {}", if options.wrap { "wrapping" } else { "bounded" }).as_str(),
+                )?;
+                let mask_name = match &selected_mask {
+                    Some((i, _)) => mask_paths[*i].file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+                    None => "none".to_string(),
+                };
+                render_text(
+                    canvas,
+                    mask_toggle_rect.x(),
+                    mask_toggle_rect.y(),
+                    format!("Mask: {mask_name}").as_str(),
+                )?;
+                if let Some((_, Err(e))) = &selected_mask {
+                    render_colored_text(canvas, mask_toggle_rect.right() + 20, mask_toggle_rect.y(), e.to_string().as_str(), Color::RGB(230, 80, 80))?;
+                }
+                render_text(
+                    canvas,
+                    mode_toggle_rect.x(),
+                    mode_toggle_rect.y(),
+                    format!("Mode: {}", if endless_mode { "endless" } else { "classic" }).as_str(),
+                )?;
+                if let Some(e) = density {
+                    render_colored_text(canvas, mode_toggle_rect.right() + 20, mode_toggle_rect.y(), e.as_str(), Color::RGB(230, 80, 80))?;
+                }
+                render_text(
+                    canvas,
+                    theme_toggle_rect.x(),
+                    theme_toggle_rect.y(),
+                    format!("Theme: {}", themes[theme_index].name).as_str(),
+                )?;
+                if let Some(e) = &theme_error {
+                    render_colored_text(canvas, theme_toggle_rect.right() + 20, theme_toggle_rect.y(), e.as_str(), Color::RGB(230, 80, 80))?;
+                }
                 
 
                 canvas.present();
@@ -184,130 +659,363 @@ fn main() -> Result<(), String> {
 
             GameState::InGame => {
                 for event in event_pump.poll_iter() {
+                    if let Event::MouseMotion { x, y, .. } = event {
+                        mouse = Point::new(x, y);
+                    }
+                    if let Event::Window { win_event: WindowEvent::Resized(width, height), .. } = event {
+                        if resize_board(&board, &mut layout, &mut camera, width as u32, height as u32) {
+                            tile_size = layout.tile_size();
+                            textures = board_textures(canvas, assets.creator(), &themes[theme_index], tile_size)?;
+                            // the next game from the menu starts out at the size the window settled on
+                            boxes[5].1 = format!("Tile size: {tile_size}");
+                        }
+                        continue;
+                    }
+                    if camera_event(&mut camera, &event, mouse) {
+                        continue;
+                    }
                     match event {
                         Event::Quit { .. }
                         | Event::KeyDown {
                             keycode: Some(Keycode::Escape),
                             ..
-                        } => break 'game_loop,
+                        } => return Ok(Flow::Quit),
+                        Event::KeyDown {
+                            keycode: Some(Keycode::PageUp),
+                            ..
+                        } => layer = layer.saturating_sub(1),
+                        Event::KeyDown {
+                            keycode: Some(Keycode::PageDown),
+                            ..
+                        } => layer = (layer + 1).min(board.layers() - 1),
+                        Event::KeyDown {
+                            keycode: Some(keycode),
+                            keymod,
+                            ..
+                        } => {
+                            let mut current = cursor.unwrap_or_else(|| Cursor::new(&board, layer));
+                            let (i, j) = (current.i, current.j);
+                            match current.key(&board, keycode, keymod) {
+                                Some(CursorAction::Reveal) => {
+                                    start_time.get_or_insert_with(Instant::now);
+                                    board.protect_first_click(i, j);
+                                    board.resolve_click(i, j);
+                                },
+                                Some(CursorAction::Flag) => board.resolve_flag(i, j),
+                                Some(CursorAction::Chord) => board.chord(i, j, chord_mismatch),
+                                // keys that don't do anything shouldn't bring up the cursor
+                                None if cursor.is_none() && (current.i, current.j) == (i, j) => continue,
+                                None => {},
+                            };
+                            cursor = Some(current);
+                            layer = current.layer(&board);
+                            camera.show(layout.tile_rect(board_origin(&board, &layout), current.i, current.j));
+                        },
                         Event::MouseButtonDown {
-                            mouse_btn: MouseButton::Left,
+                            mouse_btn,
+                            x,
+                            y,
+                            ..
+                        } => {
+                            if let MouseButton::Left = mouse_btn {
+                                if drag.press(&mut camera, Point::new(x, y)) {
+                                    continue;
+                                }
+                            }
+                            let tile = tile_under(&board, &layout, &camera, layer, x, y);
+                            let on_face = face_rect(camera.viewport().width()).contains_point(Point::new(x, y));
+                            if let Some(Click::Flag((i, j))) = buttons.press(mouse_btn, tile, &mut pressed_tile, on_face) {
+                                board.resolve_flag(i, j);
+                            }
+                        },
+                        Event::MouseMotion {
                             x,
                             y,
+                            xrel,
+                            yrel,
                             ..
                         } => {
-                            pressed_i = Some((y / TILE_SIZE as i32) as usize);
-                            pressed_j = Some((x / TILE_SIZE as i32) as usize);
+                            if drag.motion(&mut camera, Point::new(x, y), xrel, yrel, buttons.left && !buttons.chording) {
+                                pressed_tile = None;
+                                buttons.face_pressed = false;
+                            } else {
+                                buttons.hover(tile_under(&board, &layout, &camera, layer, x, y), &mut pressed_tile);
+                            }
                         },
                         Event::MouseButtonUp {
-                            mouse_btn: MouseButton::Left,
+                            mouse_btn,
                             x,
                             y,
                             ..
                         } => {
-                            let i = (y / TILE_SIZE as i32) as usize;
-                            let j = (x / TILE_SIZE as i32) as usize;
-                            match (pressed_i, pressed_j) {
-                                (Some(i1), Some(j1)) => {
-                                    if i1 == i && j1 == j {
-                                        board.resolve_click(&mut game_state, i, j);
-                                    }
-                                }
-                                _ => continue,
+                            let dragged = mouse_btn == MouseButton::Left && drag.release();
+                            let tile = tile_under(&board, &layout, &camera, layer, x, y);
+                            let on_face = face_rect(camera.viewport().width()).contains_point(Point::new(x, y));
+                            match buttons.release(mouse_btn, tile, &mut pressed_tile, on_face, dragged) {
+                                Some(Click::Reveal((i, j))) => {
+                                    start_time.get_or_insert_with(Instant::now);
+                                    board.protect_first_click(i, j);
+                                    board.resolve_click(i, j);
+                                },
+                                Some(Click::Chord((i, j))) => board.chord(i, j, chord_mismatch),
+                                Some(Click::Face) => {
+                                    board = board.restart()?;
+                                    start_time = None;
+                                },
+                                Some(Click::Flag(_)) | None => {},
                             };
                         },
+                        _ => {},
+                    }
+                }
+
+                // keep the cursor on the layer shown after switching with page up and down
+                if let Some(current) = cursor.as_mut() {
+                    current.set_layer(&board, layer);
+                }
+
+                match GameState::from(board.state()) {
+                    GameState::Won => {
+                        end_rect = &won_rect;
+                        end_texture = &won_texture;
+                        end_time = Some(Instant::now());
+                        game_state.push(GameState::Won);
+                    },
+                    GameState::GameOver => {
+                        end_time = Some(Instant::now());
+                        game_state.push(GameState::GameOver);
+                    },
+                    _ => {},
+                };
+
+                // tiles that are drawn pressed down while a mouse button is held
+                let mut pressed_preview = Vec::new();
+                if let Some((i, j)) = pressed_tile {
+                    pressed_preview.push((i, j));
+                    if buttons.chording {
+                        pressed_preview.append(&mut board.neighbours(i, j));
+                    }
+                }
+
+                let seconds = start_time.map_or(0, |start| start.elapsed().as_secs());
+                draw_game(canvas, &board, &layout, &textures, board.state(), &pressed_preview, buttons.face_pressed, seconds, layer, &camera, cursor)?;
+                canvas.present();
+            },
+
+            GameState::Endless => {
+                let field = endless.as_mut().expect("endless game without a field");
+                let tile = tile_size as i64;
+                for event in event_pump.poll_iter() {
+                    match event {
+                        Event::Quit { .. }
+                        | Event::KeyDown {
+                            keycode: Some(Keycode::Escape),
+                            ..
+                        } => return Ok(Flow::Quit),
+                        Event::Window {
+                            win_event: WindowEvent::Resized(width, height),
+                            ..
+                        } => resize_endless(&mut endless_view, &mut endless_camera, width as u32, height as u32),
+                        Event::KeyDown {
+                            keycode: Some(keycode),
+                            ..
+                        } => {
+                            match keycode {
+                                Keycode::Left | Keycode::A => endless_camera.0 -= tile,
+                                Keycode::Right | Keycode::D => endless_camera.0 += tile,
+                                Keycode::Up | Keycode::W => endless_camera.1 -= tile,
+                                Keycode::Down | Keycode::S => endless_camera.1 += tile,
+                                _ => {},
+                            };
+                        },
+                        Event::MouseWheel {
+                            x,
+                            y,
+                            ..
+                        } => {
+                            endless_camera.0 += x as i64 * 3 * tile;
+                            endless_camera.1 -= y as i64 * 3 * tile;
+                        },
                         Event::MouseButtonDown {
-                            mouse_btn: MouseButton::Right,
+                            mouse_btn,
+                            x,
+                            y,
+                            ..
+                        } => {
+                            let tile = endless_tile_at(endless_camera, tile_size, x, y);
+                            let on_face = face_rect(endless_view.0).contains_point(Point::new(x, y));
+                            if let Some(Click::Flag((tx, ty))) = buttons.press(mouse_btn, tile, &mut endless_pressed, on_face) {
+                                field.resolve_flag(tx, ty);
+                            }
+                        },
+                        Event::MouseMotion {
                             x,
                             y,
                             ..
                         } => {
-                            let i = (y / TILE_SIZE as i32) as usize;
-                            let j = (x / TILE_SIZE as i32) as usize;
-                            board.resolve_flag(i, j);
+                            buttons.hover(endless_tile_at(endless_camera, tile_size, x, y), &mut endless_pressed);
+                        },
+                        Event::MouseButtonUp {
+                            mouse_btn,
+                            x,
+                            y,
+                            ..
+                        } => {
+                            let tile = endless_tile_at(endless_camera, tile_size, x, y);
+                            let on_face = face_rect(endless_view.0).contains_point(Point::new(x, y));
+                            match buttons.release(mouse_btn, tile, &mut endless_pressed, on_face, false) {
+                                Some(Click::Reveal((tx, ty))) => field.resolve_click(tx, ty),
+                                Some(Click::Chord((tx, ty))) => field.chord(tx, ty),
+                                Some(Click::Face) => {
+                                    *field = field.restart();
+                                    field.resolve_click(0, 0);
+                                    endless_camera = (-((endless_view.0 - tile_size) as i64) / 2, -((endless_view.1 - tile_size) as i64) / 2);
+                                    start_time = Some(Instant::now());
+                                },
+                                Some(Click::Flag(_)) | None => {},
+                            };
                         },
                         _ => {},
                     }
                 }
-
-                if let GameState::GameOver = game_state {
-                    continue;
-                }
-        
-                game_state = board.check_game_state();
-                if let GameState::Won = game_state {
-                    end_rect = &won_rect;
-                    end_texture = &won_texture;
+                if let board::GameState::GameOver = field.game_state() {
+                    game_state.push(GameState::GameOver);
+                    end_time = Some(Instant::now());
                 }
 
-                canvas.set_draw_color(Color::RGB(0, 0, 0));
-                canvas.clear();
-                for row in board.iter_field() {
-                    for tile in row.iter() {
-                        match tile.state() {
-                            TileState::Revealed => {
-                                canvas.copy(
-                                    &revealed_texture,
-                                    None,
-                                    tile.rect(),
-                                )?;
-                                if let TileValue::Adjacent(x) = tile.value() {
-                                    canvas.copy(
-                                        number_textures.get(x as usize).expect(format!("texture for index {x} doesnt exist").as_str()),
-                                        None,
-                                        Rect::from_center(tile.center(), surface_rect.width(), surface_rect.height())
-                                    )?;
-                                } 
-                            },
-                            TileState::Flagged => {
-                                canvas.copy(
-                                    &flag_texture,
-                                    None,
-                                    tile.rect(),
-                                )?;
-                            },
-                            TileState::Hidden => {
-                                canvas.copy(
-                                    &hidden_texture,
-                                    None,
-                                    tile.rect(),
-                                )?;
-                            },
-                        }           
+                // forget whatever was cleared far away from the middle of the view
+                let center_x = (endless_camera.0 + endless_view.0 as i64 / 2).div_euclid(tile);
+                let center_y = (endless_camera.1 + endless_view.1 as i64 / 2).div_euclid(tile);
+                field.evict(center_x, center_y);
+
+                let mut pressed_preview = Vec::new();
+                if let Some((tx, ty)) = endless_pressed {
+                    pressed_preview.push((tx, ty));
+                    if buttons.chording {
+                        pressed_preview.extend(endless::neighbours(tx, ty));
                     }
                 }
-        
+                let seconds = start_time.map_or(0, |start| start.elapsed().as_secs());
+                draw_endless(canvas, field, &textures, field.game_state(), &pressed_preview, buttons.face_pressed, seconds, endless_camera, endless_view, tile_size)?;
                 canvas.present();
             },
 
             GameState::GameOver
             | GameState::Won => {
+                let (view_width, view_height) = match endless {
+                    Some(_) => endless_view,
+                    None => (camera.viewport().width(), camera.viewport().height()),
+                };
                 for event in event_pump.poll_iter() {
+                    // the finished board can still be looked around
+                    if let Event::MouseMotion { x, y, .. } = event {
+                        mouse = Point::new(x, y);
+                    }
+                    if let Event::Window { win_event: WindowEvent::Resized(width, height), .. } = event {
+                        if endless.is_some() {
+                            resize_endless(&mut endless_view, &mut endless_camera, width as u32, height as u32);
+                        } else if resize_board(&board, &mut layout, &mut camera, width as u32, height as u32) {
+                            tile_size = layout.tile_size();
+                            textures = board_textures(canvas, assets.creator(), &themes[theme_index], tile_size)?;
+                            boxes[5].1 = format!("Tile size: {tile_size}");
+                        }
+                        continue;
+                    }
+                    if endless.is_none() && camera_event(&mut camera, &event, mouse) {
+                        continue;
+                    }
                     match event {
                         Event::Quit { .. }
                         | Event::KeyDown {
                                 keycode: Some(Keycode::Escape),
                                 ..
-                        } => break 'game_loop,
+                        } => return Ok(Flow::Quit),
+                        Event::KeyDown {
+                            keycode: Some(Keycode::PageUp),
+                            ..
+                        } => layer = layer.saturating_sub(1),
+                        Event::KeyDown {
+                            keycode: Some(Keycode::PageDown),
+                            ..
+                        } => layer = (layer + 1).min(board.layers() - 1),
                         Event::KeyDown {
                                 keycode: Some(Keycode::Return),
                                 ..
                         } => {
                             end_rect = &game_over_rect;
                             end_texture = &game_over_texture;
-                            game_state = GameState::Menu;
+                            game_state.reset(GameState::Menu);
                             canvas
                                 .window_mut()
                                 .set_size(MENU_WIDTH, MENU_HEIGHT)
                                 .map_err(|e| e.to_string())?;
                         },
+                        Event::MouseButtonDown {
+                            mouse_btn: MouseButton::Left,
+                            x,
+                            y,
+                            ..
+                        } => {
+                            buttons.face_pressed = face_rect(view_width).contains_point(Point::new(x, y));
+                            if endless.is_none() && !buttons.face_pressed {
+                                drag.press(&mut camera, Point::new(x, y));
+                            }
+                        },
+                        Event::MouseMotion {
+                            x,
+                            y,
+                            xrel,
+                            yrel,
+                            ..
+                        } => {
+                            drag.motion(&mut camera, Point::new(x, y), xrel, yrel, true);
+                        },
+                        Event::MouseButtonUp {
+                            mouse_btn: MouseButton::Left,
+                            x,
+                            y,
+                            ..
+                        } => {
+                            drag.release();
+                            if buttons.face_pressed && face_rect(view_width).contains_point(Point::new(x, y)) {
+                                end_time = None;
+                                end_rect = &game_over_rect;
+                                end_texture = &game_over_texture;
+                                match endless.as_mut() {
+                                    Some(field) => {
+                                        *field = field.restart();
+                                        game_state.pop();
+                                        field.resolve_click(0, 0);
+                                        endless_camera = (-((endless_view.0 - tile_size) as i64) / 2, -((endless_view.1 - tile_size) as i64) / 2);
+                                        start_time = Some(Instant::now());
+                                    },
+                                    None => {
+                                        board = board.restart()?;
+                                        start_time = None;
+                                        game_state.pop();
+                                    },
+                                };
+                            }
+                            buttons.face_pressed = false;
+                        },
                         _ => {},
                     };
                 }
-                canvas.set_draw_color(Color::RGB(50, 50, 50));
-                canvas.clear();
-        
-                let center = Rect::new(0,0,TILE_SIZE * board.tile_columns, TILE_SIZE * board.tile_rows).center();
+                let seconds = match (start_time, end_time) {
+                    (Some(start), Some(end)) => end.duration_since(start).as_secs(),
+                    _ => 0,
+                };
+                match &endless {
+                    Some(field) => draw_endless(canvas, field, &textures, field.game_state(), &[], buttons.face_pressed, seconds, endless_camera, endless_view, tile_size)?,
+                    None => draw_game(canvas, &board, &layout, &textures, board.state(), &[], buttons.face_pressed, seconds, layer, &camera, None)?,
+                };
+
+                // lay a translucent band across the board and put the end text on top
+                let center = Rect::new(0, 0, view_width, view_height + HUD_HEIGHT).center();
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(220, 220, 220, 200));
+                canvas.fill_rect(Rect::from_center(center, view_width, end_rect.height() + 10))?;
+                canvas.set_blend_mode(BlendMode::None);
                 canvas.copy(
                     end_texture,
                     None,
@@ -316,13 +1024,7 @@ fn main() -> Result<(), String> {
         
                 canvas.present();
             },
-
-            GameState::Won => {
-                println!("you've beaten the game :)");
-                break 'game_loop;
-            },
         }
-    }
-
-    Ok(())
+        Ok(Flow::Continue)
+    })
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::minesweeper::error::AssetError;
+
+/// The playable cells of a board that isn't a full rectangle.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mask {
+    cells: Vec<Vec<bool>>,
+}
+
+impl Mask {
+    /// Reads a mask from text, one line per row. `#`, `x`, `X`, `o` and `1` mark playable cells,
+    /// anything else is a hole. Shorter lines are padded with holes.
+    pub fn from_text(text: &str) -> Result<Self, AssetError> {
+        let mut cells: Vec<Vec<bool>> = text
+            .lines()
+            .map(|line| line.chars().map(|c| matches!(c, '#' | 'x' | 'X' | 'o' | '1')).collect())
+            .collect();
+        while let Some(false) = cells.last().map(|row| row.contains(&true)) {
+            cells.pop();
+        }
+        let columns = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in cells.iter_mut() {
+            row.resize(columns, false);
+        }
+        Self::from_cells(cells)
+    }
+
+    /// Loads a mask from a text file.
+    pub fn load(path: &Path) -> Result<Self, AssetError> {
+        Self::from_text(&std::fs::read_to_string(path).map_err(|e| AssetError::Read(path.to_path_buf(), e))?)
+    }
+
+    /// Creates a mask from rows of cells, where `true` is playable. Every row must be equally long.
+    pub fn from_cells(cells: Vec<Vec<bool>>) -> Result<Self, AssetError> {
+        let mask = Self { cells };
+        if mask.playable_count() == 0 {
+            return Err(AssetError::EmptyMask);
+        }
+        Ok(mask)
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.cells.len() as u32
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.cells.first().map_or(0, |row| row.len()) as u32
+    }
+
+    pub fn is_playable(&self, i: usize, j: usize) -> bool {
+        self.cells[i][j]
+    }
+
+    pub fn playable_count(&self) -> u32 {
+        self.cells.iter().flatten().filter(|cell| **cell).count() as u32
+    }
+}
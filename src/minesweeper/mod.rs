@@ -0,0 +1,21 @@
+//! The rules of minesweeper live in `board`, `bits`, `mask` and `endless`, which don't need SDL, and so do
+//! `net`, both sides of the server protocol, the keyboard `cursor` and the `error` types.
+//! Everything else lays the game out, draws it or reads input for it.
+
+pub mod bits;
+pub mod board;
+pub mod mask;
+pub mod endless;
+pub mod error;
+pub mod net;
+pub mod cursor;
+#[cfg(feature = "sdl")]
+pub mod camera;
+#[cfg(feature = "sdl")]
+pub mod layout;
+#[cfg(feature = "sdl")]
+pub mod my_textures;
+#[cfg(feature = "sdl")]
+pub mod render;
+#[cfg(feature = "sdl")]
+pub mod theme;
@@ -1,94 +1,152 @@
+use std::path::Path;
 use sdl2::{
-    pixels::Color,
-    rect::{Rect},
-    render::{Canvas, Texture, TextureCreator},
+    image::LoadSurface,
+    pixels::{Color, PixelFormatEnum},
+    rect::{Point, Rect},
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    surface::Surface,
     video::{Window, WindowContext},
 };
-use crate::TILE_SIZE;
+use crate::minesweeper::board::Topology;
+use crate::minesweeper::error::AssetError;
+use crate::minesweeper::mask::Mask;
+use crate::minesweeper::layout::tile_contains;
+use crate::app::asset_path;
 
-pub fn tile_textures<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a mut TextureCreator<WindowContext>) -> Result<(Texture<'a>, Texture<'a>), String> {
+/// Creates flat hidden, revealed and flag tiles in the given colours. Hidden tiles and flags get a
+/// border so neighbouring tiles can be told apart, and the flag is drawn on top of a hidden tile.
+pub fn tile_textures<'a> (
+    canvas: &mut Canvas<Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    tile_size: u32,
+    hidden: Color,
+    revealed: Color,
+    flag: Color,
+    border: Color,
+) -> Result<(Texture<'a>, Texture<'a>, Texture<'a>), String> {
     enum TextureKind {
         Hidden,
-        Revealed
+        Revealed,
+        Flag,
     }
     let mut hidden_texture = texture_creator
-        .create_texture_target(None, TILE_SIZE, TILE_SIZE)
+        .create_texture_target(None, tile_size, tile_size)
         .map_err(|e| e.to_string())?;
     let mut revealed_texture = texture_creator
-        .create_texture_target(None, TILE_SIZE, TILE_SIZE)
+        .create_texture_target(None, tile_size, tile_size)
+        .map_err(|e| e.to_string())?;
+    let mut flag_texture = texture_creator
+        .create_texture_target(None, tile_size, tile_size)
         .map_err(|e| e.to_string())?;
 
-    let textures = vec![
+    let textures = [
         (&mut hidden_texture, TextureKind::Hidden),
-        (&mut revealed_texture, TextureKind::Revealed)
+        (&mut revealed_texture, TextureKind::Revealed),
+        (&mut flag_texture, TextureKind::Flag),
     ];
+    let size = tile_size as i32;
     canvas
         .with_multiple_texture_canvas(textures.iter(), |texture_canvas, user_context| {
-            texture_canvas.set_draw_color(Color::RGB(0, 0, 0));
-            texture_canvas.clear();
             match user_context {
-                TextureKind::Hidden => {
-                    texture_canvas.set_draw_color(Color::RGB(220, 220, 220));
-                    texture_canvas
-                        .fill_rect(Rect::new(0, 0, TILE_SIZE, TILE_SIZE))
-                        .expect("could not draw point");
-                    texture_canvas.set_draw_color(Color::RGB(170, 170, 170));
+                TextureKind::Hidden
+                | TextureKind::Flag => {
+                    texture_canvas.set_draw_color(border);
+                    texture_canvas.clear();
+                    texture_canvas.set_draw_color(hidden);
                     texture_canvas
-                        .fill_rect(Rect::new(1, 1, TILE_SIZE -2, TILE_SIZE -2))
+                        .fill_rect(Rect::new(1, 1, tile_size - 2, tile_size - 2))
                         .expect("could not draw point");
                 },
                 TextureKind::Revealed => {
-                    // texture_canvas.set_draw_color(Color::RGB(80, 80, 80));
-                    // texture_canvas
-                    //     .fill_rect(Rect::new(0, 0, TILE_SIZE, TILE_SIZE))
-                    //     .expect("could not draw point");
-                    texture_canvas.set_draw_color(Color::RGB(220, 220, 220));
-                    texture_canvas
-                        .fill_rect(Rect::new(0, 0, TILE_SIZE, TILE_SIZE))
-                        .expect("could not draw point");
+                    texture_canvas.set_draw_color(revealed);
+                    texture_canvas.clear();
                 },
             };
+            if let TextureKind::Flag = user_context {
+                // a pole with a pennant hanging to the right of it
+                let pole = size / 3;
+                texture_canvas.set_draw_color(border);
+                texture_canvas
+                    .draw_line(Point::new(pole, size / 5), Point::new(pole, size - size / 5))
+                    .expect("could not draw line");
+                texture_canvas.set_draw_color(flag);
+                let height = size / 3;
+                for dy in 0..height {
+                    let width = (size / 2) * (height - dy) / height;
+                    texture_canvas
+                        .draw_line(Point::new(pole + 1, size / 5 + dy), Point::new(pole + 1 + width, size / 5 + dy))
+                        .expect("could not draw line");
+                }
+            }
         })
         .map_err(|e| e.to_string())?;
 
-    Ok((hidden_texture, revealed_texture))
+    Ok((hidden_texture, revealed_texture, flag_texture))
 }
 
 pub fn render_text(canvas: &mut Canvas<Window>, x: i32, y: i32, text: &str) -> Result<Rect, String> {
-    let mut texture_creator = canvas.texture_creator();
-    let (text_texture, text_rect) = text_texture(&mut texture_creator, text, 18)?;
+    let texture_creator = canvas.texture_creator();
+    let (text_texture, text_rect) = text_texture(&texture_creator, text, 18)?;
+    canvas.copy(
+        &text_texture,
+        None,
+        Rect::new(x, y, text_rect.width(), text_rect.height()),
+    )?;
+    Ok(text_rect)
+}
+
+pub fn render_colored_text(canvas: &mut Canvas<Window>, x: i32, y: i32, text: &str, color: Color) -> Result<Rect, String> {
+    let texture_creator = canvas.texture_creator();
+    let (text_texture, text_rect) = colored_text_texture(&texture_creator, text, 18, color)?;
     canvas.copy(
         &text_texture,
         None,
         Rect::new(x, y, text_rect.width(), text_rect.height()),
     )?;
-    return Ok(text_rect);
+    Ok(text_rect)
+}
+
+pub fn text_texture<'a> (texture_creator: &'a TextureCreator<WindowContext>, text: &str, size: u16) -> Result<(Texture<'a>, Rect), String> {
+    colored_text_texture(texture_creator, text, size, Color::BLACK)
 }
 
-pub fn text_texture<'a> (texture_creator: &'a mut TextureCreator<WindowContext>, text: &str, size: u16) -> Result<(Texture<'a>, Rect), String> {
+pub fn colored_text_texture<'a> (texture_creator: &'a TextureCreator<WindowContext>, text: &str, size: u16, color: Color) -> Result<(Texture<'a>, Rect), String> {
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
-    let font = ttf_context.load_font("assets/Monaco.ttf", size)?;
+    let font = ttf_context.load_font(asset_path("assets/Monaco.ttf"), size)?;
 
     let text_surface = font.render(text)
-        .solid(Color::BLACK)
+        .solid(color)
         .map_err(|e| e.to_string())?;
 
     let menu_rect = text_surface.rect();
     let menu_texture = texture_creator
         .create_texture_from_surface(text_surface)
         .map_err(|e| e.to_string())?;
-    return Ok((menu_texture, menu_rect));
+    Ok((menu_texture, menu_rect))
 }
 
-pub fn number_textures<'a> (texture_creator: &'a mut TextureCreator<WindowContext>) -> Result<(Vec<Texture<'a>>, Rect), String> {
+/// Point size of text that fits on a tile, 16 for the default 20 pixel tiles.
+pub fn glyph_size(tile_size: u32) -> u16 {
+    (tile_size * 4 / 5).max(1) as u16
+}
+
+/// Creates textures for all numbers from 0 up to and including `max`, written in `font` at a size
+/// that fits on a tile and coloured by `color`.
+pub fn number_textures<'a> (
+    texture_creator: &'a TextureCreator<WindowContext>,
+    tile_size: u32,
+    max: u32,
+    font: &str,
+    color: impl Fn(u32) -> Color,
+) -> Result<(Vec<Texture<'a>>, Rect), String> {
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
-    let font = ttf_context.load_font("assets/Monaco.ttf", 16)?;
+    let font = ttf_context.load_font(asset_path(font), glyph_size(tile_size))?;
     
     let mut number_textures = Vec::new();
 
-    for i in 0..=9 {
+    for i in 0..=max {
         let text_surface = font.render(format!("{i}").as_str())
-            .solid(Color::BLACK)
+            .solid(color(i))
             .map_err(|e| e.to_string())?;
         
         number_textures.push(texture_creator
@@ -102,4 +160,169 @@ pub fn number_textures<'a> (texture_creator: &'a mut TextureCreator<WindowContex
     let surface_rect = text_surface.rect();
 
     Ok((number_textures, surface_rect))
-}
\ No newline at end of file
+}
+pub fn question_texture<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, tile_size: u32, hidden_texture: &Texture) -> Result<Texture<'a>, String> {
+    let glyph_creator = canvas.texture_creator();
+    let (glyph_texture, glyph_rect) = text_texture(&glyph_creator, "?", glyph_size(tile_size))?;
+
+    let mut question_texture = texture_creator
+        .create_texture_target(None, tile_size, tile_size)
+        .map_err(|e| e.to_string())?;
+    let tile_rect = Rect::new(0, 0, tile_size, tile_size);
+    canvas
+        .with_texture_canvas(&mut question_texture, |texture_canvas| {
+            texture_canvas
+                .copy(hidden_texture, None, tile_rect)
+                .expect("could not draw hidden tile");
+            texture_canvas
+                .copy(&glyph_texture, None, Rect::from_center(tile_rect.center(), glyph_rect.width(), glyph_rect.height()))
+                .expect("could not draw question mark");
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(question_texture)
+}
+
+/// Creates the textures for an uncovered mine and for the mine that ended the game.
+pub fn mine_textures<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, tile_size: u32, revealed_texture: &Texture) -> Result<(Texture<'a>, Texture<'a>), String> {
+    enum TextureKind {
+        Mine,
+        Exploded,
+    }
+    let mut mine_texture = texture_creator
+        .create_texture_target(None, tile_size, tile_size)
+        .map_err(|e| e.to_string())?;
+    let mut exploded_texture = texture_creator
+        .create_texture_target(None, tile_size, tile_size)
+        .map_err(|e| e.to_string())?;
+
+    let textures = [
+        (&mut mine_texture, TextureKind::Mine),
+        (&mut exploded_texture, TextureKind::Exploded),
+    ];
+    let center = (tile_size / 2) as i32;
+    let radius = (tile_size / 4) as i32;
+    canvas
+        .with_multiple_texture_canvas(textures.iter(), |texture_canvas, user_context| {
+            match user_context {
+                TextureKind::Mine => {
+                    texture_canvas
+                        .copy(revealed_texture, None, None)
+                        .expect("could not draw revealed tile");
+                },
+                TextureKind::Exploded => {
+                    texture_canvas.set_draw_color(Color::RGB(220, 0, 0));
+                    texture_canvas.clear();
+                },
+            };
+            texture_canvas.set_draw_color(Color::RGB(0, 0, 0));
+            for dy in -radius..=radius {
+                let dx = (((radius * radius - dy * dy) as f64).sqrt()) as i32;
+                texture_canvas
+                    .draw_line(Point::new(center - dx, center + dy), Point::new(center + dx, center + dy))
+                    .expect("could not draw line");
+            }
+            texture_canvas
+                .draw_line(Point::new(center, center - radius - 2), Point::new(center, center + radius + 2))
+                .expect("could not draw line");
+            texture_canvas
+                .draw_line(Point::new(center - radius - 2, center), Point::new(center + radius + 2, center))
+                .expect("could not draw line");
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok((mine_texture, exploded_texture))
+}
+
+/// Creates the texture for a flag that was placed on a tile without a mine.
+pub fn wrong_flag_texture<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, tile_size: u32, flag_texture: &Texture) -> Result<Texture<'a>, String> {
+    let mut wrong_flag_texture = texture_creator
+        .create_texture_target(None, tile_size, tile_size)
+        .map_err(|e| e.to_string())?;
+    let size = tile_size as i32 - 3;
+    canvas
+        .with_texture_canvas(&mut wrong_flag_texture, |texture_canvas| {
+            texture_canvas
+                .copy(flag_texture, None, None)
+                .expect("could not draw flag tile");
+            texture_canvas.set_draw_color(Color::RGB(220, 0, 0));
+            for offset in 0..2 {
+                texture_canvas
+                    .draw_line(Point::new(2 + offset, 2), Point::new(size + offset, size))
+                    .expect("could not draw line");
+                texture_canvas
+                    .draw_line(Point::new(size + offset, 2), Point::new(2 + offset, size))
+                    .expect("could not draw line");
+            }
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(wrong_flag_texture)
+}
+
+/// Cuts a hexagon out of a square tile texture, leaving the corners transparent.
+pub fn hex_texture<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, tile_size: u32, square_texture: &Texture) -> Result<Texture<'a>, String> {
+    let mut hex_texture = texture_creator
+        .create_texture_target(PixelFormatEnum::RGBA8888, tile_size, tile_size)
+        .map_err(|e| e.to_string())?;
+    hex_texture.set_blend_mode(BlendMode::Blend);
+    let size = tile_size as i32;
+    let inside = |x: i32, y: i32| tile_contains(Topology::Hex, x, y, tile_size);
+    canvas
+        .with_texture_canvas(&mut hex_texture, |texture_canvas| {
+            texture_canvas.set_blend_mode(BlendMode::None);
+            texture_canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+            texture_canvas.clear();
+            texture_canvas
+                .copy(square_texture, None, None)
+                .expect("could not draw square tile");
+            for y in 0..size {
+                for x in 0..size {
+                    if !inside(x, y) {
+                        texture_canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+                    } else if !inside(x - 1, y) || !inside(x + 1, y) || !inside(x, y - 1) || !inside(x, y + 1) {
+                        // outline, so neighbouring hexagons can be told apart
+                        texture_canvas.set_draw_color(Color::RGBA(90, 90, 90, 255));
+                    } else {
+                        continue;
+                    }
+                    texture_canvas
+                        .draw_point(Point::new(x, y))
+                        .expect("could not draw point");
+                }
+            }
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(hex_texture)
+}
+
+/// Reads a mask from an image, where every opaque dark pixel is a playable cell.
+pub fn mask_from_image(path: &Path) -> Result<Mask, AssetError> {
+    let surface = Surface::from_file(path)
+        .and_then(|surface| surface.convert_format(PixelFormatEnum::RGBA32))
+        .map_err(|e| AssetError::Sdl(format!("{}: {e}", path.display())))?;
+    let (width, height, pitch) = (surface.width() as usize, surface.height() as usize, surface.pitch() as usize);
+    let cells = surface.with_lock(|pixels| {
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let pixel = &pixels[y * pitch + x * 4..y * pitch + x * 4 + 4];
+                        let brightness = (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3;
+                        pixel[3] >= 128 && brightness < 128
+                    })
+                    .collect()
+            })
+            .collect()
+    });
+    Mask::from_cells(cells)
+}
+
+/// Loads a mask from a `.png` image or a text file.
+pub fn load_mask(path: &Path) -> Result<Mask, AssetError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") | Some("PNG") => mask_from_image(path),
+        _ => Mask::load(path),
+    }
+}
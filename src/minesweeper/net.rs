@@ -0,0 +1,298 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::minesweeper::board::{
+    Action,
+    Board,
+    BoardOptions,
+    GameState,
+    Topology,
+    check_size,
+    validate_settings,
+};
+use crate::minesweeper::error::{EngineError, Error, ProtocolError};
+
+/// Where the server listens, unless a client is told otherwise.
+pub const SERVER_ADDRESS: &str = "192.168.178.25:2024";
+/// Largest payload of a UDP datagram. The whole board is sent in a single one.
+const MAX_DATAGRAM: usize = 65507;
+/// How long a client waits for the server to answer before it gives up.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A game played against the server. Every action is checked by the server before the client
+/// applies it to its own copy of the board.
+pub struct Connection {
+    socket: UdpSocket,
+}
+
+impl Connection {
+    /// Asks the server at `address` for a new board, returning the connection along with the board.
+    /// A server that doesn't answer within a few seconds, here or later on, is given up on with
+    /// `ProtocolError::NoAnswer`.
+    pub fn start(address: impl ToSocketAddrs, rows: u32, columns: u32, bombs: u32, wrap: bool) -> Result<(Self, Board), Error> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(address)?;
+        socket.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+
+        // the first field used to be the tile size in pixels, the server doesn't look at it
+        let message: (u32, u32, u32, u32, bool) = (0, rows, columns, bombs, wrap);
+        send(&socket, &message)?;
+
+        let reply = receive(&socket)?;
+        if let Ok(false) = parse(&reply) {
+            return Err(ProtocolError::Rejected.into());
+        }
+        let board: Board = parse(&reply)?;
+        Ok((Self { socket }, board))
+    }
+
+    /// Sends an action to the server and returns whether it was accepted.
+    pub fn validate(&self, action: Action) -> Result<bool, Error> {
+        send(&self.socket, &action)?;
+        Ok(parse(&receive(&self.socket)?)?)
+    }
+
+    /// Tells the server the player quits. The server ends the session on a quit whatever it
+    /// answers, so the answer is of no interest.
+    pub fn quit(&self) -> Result<(), Error> {
+        self.validate(Action::Quit)?;
+        Ok(())
+    }
+
+    /// Sends a move to the server and, once it is accepted, makes it on `board` just like the server
+    /// does on its copy. Returns whether it was accepted.
+    pub fn play(&self, board: &mut Board, action: Action) -> Result<bool, Error> {
+        let valid = self.validate(action)?;
+        if valid {
+            board.apply(action);
+        }
+        Ok(valid)
+    }
+}
+
+/// What a single client may ask of the server. The defaults keep every board within one datagram
+/// and leave plenty of room for a player clicking fast.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Most tiles a board may have.
+    pub max_tiles: u32,
+    pub max_bombs: u32,
+    /// Moves a client may send in a row before it has to slow down.
+    pub burst: u32,
+    /// Moves per second a client may keep sending.
+    pub moves_per_second: u32,
+    /// How long the server waits for the next message before it gives up on the client.
+    pub idle_timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_tiles: 10_000,
+            max_bombs: 8_500,
+            burst: 100,
+            moves_per_second: 20,
+            idle_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Tokens for moves, refilled at a steady rate up to the burst size.
+struct RateLimit {
+    tokens: f64,
+    burst: f64,
+    per_second: f64,
+    last: Instant,
+}
+
+impl RateLimit {
+    fn new(limits: &Limits) -> Self {
+        Self {
+            tokens: limits.burst as f64,
+            burst: limits.burst as f64,
+            per_second: limits.moves_per_second as f64,
+            last: Instant::now(),
+        }
+    }
+
+    /// Takes a token for a move, or returns false when there is none left.
+    fn allow(&mut self) -> bool {
+        let now = Instant::now();
+        self.tokens = (self.tokens + (now - self.last).as_secs_f64() * self.per_second).min(self.burst);
+        self.last = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// The other end of `Connection`. It builds the board a client asks for, sends it over and then
+/// checks every action of that client against its own copy of the board.
+pub struct Server {
+    socket: UdpSocket,
+    limits: Limits,
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self, Error> {
+        Self::with_limits(address, Limits::default())
+    }
+
+    pub fn with_limits(address: impl ToSocketAddrs, limits: Limits) -> Result<Self, Error> {
+        let socket = UdpSocket::bind(address)?;
+        Ok(Self { socket, limits })
+    }
+
+    /// The address the server is bound to, which has the actual port when it was bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Waits for a client and plays a single game with it, until the client quits or claims a win.
+    /// Messages that don't follow the protocol, moves off the board or after the game ended and
+    /// moves beyond the rate limit are all turned down without ending the game. Board requests
+    /// that can't be read or are outside of the limits are turned down as well, and the server
+    /// waits for another one. Only a failing socket, a client that can't be reached any more and
+    /// a client that stays quiet for longer than the idle timeout end the session early, while
+    /// failing to answer anyone else is ignored. Either way the server is ready for the next
+    /// client afterwards.
+    pub fn serve(&self) -> Result<(), Error> {
+        // whoever comes next may take as long as they like to ask for a board
+        self.socket.set_read_timeout(None)?;
+
+        // receive board parameters from client, anything else is turned down until they come
+        let (board, serialized, peer) = loop {
+            let mut buf = vec![0; MAX_DATAGRAM];
+            let (amt, src) = self.next_datagram(&mut buf)?;
+            match parse(&buf[..amt]).map_err(Error::from).and_then(|args| self.new_board(args)) {
+                Ok((board, serialized)) => break (board, serialized, src),
+                // answered, so the client doesn't wait for a board that never comes
+                Err(_) => self.turn_down(src),
+            }
+        };
+
+        // sending board back
+        self.socket.send_to(serialized.as_bytes(), peer)?;
+        self.play(board, peer)
+    }
+
+    /// Checks the moves of the client at `peer` until the session ends. Other clients are turned
+    /// down while it lasts.
+    fn play(&self, mut board: Board, peer: SocketAddr) -> Result<(), Error> {
+        let mut rate = RateLimit::new(&self.limits);
+        let mut last_heard = Instant::now();
+        loop {
+            // wait for client move, one that can't be read is turned down and the game goes on
+            let message = self.receive_from(peer, last_heard + self.limits.idle_timeout)?;
+            last_heard = Instant::now();
+            let action = parse(&message);
+            let state = board.state();
+
+            let valid = match action {
+                // these end the session, whether they are right or not
+                Ok(Action::Won) => return self.send_valid(peer, state == GameState::Won),
+                Ok(Action::Quit) => return self.send_valid(peer, state != GameState::Won),
+
+                _ if !rate.allow() => false,
+                Ok(action @ (Action::Reveal(i, j) | Action::ToggleFlag(i, j)))
+                    if state == GameState::InGame && board.is_playable(i as usize, j as usize) =>
+                {
+                    board.apply(action);
+                    true
+                },
+                _ => false,
+            };
+            self.send_valid(peer, valid)?;
+        }
+    }
+
+    /// Waits for the next message of `peer` until `deadline`, turning down everyone else.
+    fn receive_from(&self, peer: SocketAddr, deadline: Instant) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0; MAX_DATAGRAM];
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+            self.socket.set_read_timeout(Some(timeout))?;
+            let (amt, src) = self.next_datagram(&mut buf)?;
+            if src == peer {
+                buf.truncate(amt);
+                return Ok(buf);
+            }
+            self.turn_down(src);
+        }
+    }
+
+    /// Waits for the next datagram from anyone. Some systems report a peer that went away on the
+    /// next receive, which says nothing about the ones still there, so those reports are skipped.
+    fn next_datagram(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Error> {
+        loop {
+            match self.socket.recv_from(buf) {
+                Err(e) if matches!(e.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionRefused) => continue,
+                received => return Ok(received?),
+            }
+        }
+    }
+
+    /// Answers `false` to a peer outside of the session. Whether that reaches it doesn't matter to
+    /// the session, so a failure is ignored.
+    fn turn_down(&self, peer: SocketAddr) {
+        let _ = self.send_valid(peer, false);
+    }
+
+    /// Builds the board a client asked for, along with its JSON, if it is within the limits.
+    fn new_board(&self, (_, rows, columns, bombs, wrap): (u32, u32, u32, u32, bool)) -> Result<(Board, String), Error> {
+        let options = BoardOptions {
+            topology: Topology::Square,
+            wrap,
+            ..BoardOptions::default()
+        };
+        let (columns, rows, bombs) = validate_settings(Some(columns), Some(rows), Some(bombs), options)?;
+        check_size(Some(rows * columns), 1, self.limits.max_tiles).map_err(EngineError::setting("tiles"))?;
+        check_size(Some(bombs), 1, self.limits.max_bombs).map_err(EngineError::setting("bombs"))?;
+
+        let board = Board::with_options(rows, columns, bombs, options)?;
+        let serialized = serde_json::to_string(&board).map_err(ProtocolError::Malformed)?;
+        if serialized.len() > MAX_DATAGRAM {
+            return Err(ProtocolError::TooLong.into());
+        }
+        Ok((board, serialized))
+    }
+
+    fn send_valid(&self, peer: SocketAddr, valid: bool) -> Result<(), Error> {
+        let serialized = serde_json::to_string(&valid).map_err(ProtocolError::Malformed)?;
+        self.socket.send_to(serialized.as_bytes(), peer)?;
+        Ok(())
+    }
+}
+
+fn send(socket: &UdpSocket, message: &impl Serialize) -> Result<(), Error> {
+    let serialized = serde_json::to_string(message).map_err(ProtocolError::Malformed)?;
+    socket.send(serialized.as_bytes())?;
+    Ok(())
+}
+
+/// Waits for a single message from the server. Anything longer than a datagram can hold is cut off.
+fn receive(socket: &UdpSocket) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; MAX_DATAGRAM];
+    let amt = socket.recv(&mut buf).map_err(|e| match e.kind() {
+        // the timeout shows up as either, depending on the system
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ProtocolError::NoAnswer.into(),
+        // nothing listens on the port
+        io::ErrorKind::ConnectionRefused => ProtocolError::NoAnswer.into(),
+        _ => Error::from(e),
+    })?;
+    buf.truncate(amt);
+    Ok(buf)
+}
+
+/// Reads a message as the JSON of `T`.
+fn parse<T: DeserializeOwned>(message: &[u8]) -> Result<T, ProtocolError> {
+    let text = std::str::from_utf8(message).map_err(|_| ProtocolError::NotUtf8)?;
+    serde_json::from_str(text).map_err(ProtocolError::Malformed)
+}
@@ -0,0 +1,438 @@
+use sdl2::{
+    image::LoadTexture,
+    pixels::Color,
+    rect::{Rect, Point},
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+};
+use crate::app::asset_path;
+use crate::minesweeper::board::{Board, GameState, TileState, TileValue, Topology, MAX_ADJACENT_BOMBS};
+use crate::minesweeper::camera::Camera;
+use crate::minesweeper::cursor::Cursor;
+use crate::minesweeper::endless::EndlessBoard;
+use crate::minesweeper::layout::Layout;
+use crate::minesweeper::my_textures::*;
+use crate::minesweeper::theme::{Theme, TileArt, rgb};
+
+pub const HUD_HEIGHT: u32 = 40;
+pub const FACE_SIZE: u32 = 30;
+/// Room for the three digits of the counter or the timer.
+pub const HUD_TEXT_WIDTH: u32 = 35;
+/// Space around the counter and the timer, between them and the edges as well as the face.
+pub const HUD_MARGIN: u32 = 5;
+/// Narrowest the HUD gets, with the counter and the timer on either side of the face. The window
+/// above a narrower view is this wide, with the HUD across all of it.
+pub const MIN_HUD_WIDTH: u32 = 2 * (2 * HUD_MARGIN + HUD_TEXT_WIDTH) + FACE_SIZE;
+/// Space between the current layer and the ghosted neighbouring layers of a 3D board.
+pub const LAYER_GAP: u32 = 10;
+/// Length of the longer side of the minimap.
+pub const MINIMAP_SIZE: u32 = 120;
+
+/// Size of everything below the HUD. 3D boards show the layers above and below the current one
+/// at half size to its left and right.
+pub fn view_size(board: &Board, layout: &Layout) -> (u32, u32) {
+    let (width, height) = layout.field_size();
+    match board.layers() {
+        1 => (width, height),
+        _ => (width * 2 + 2 * LAYER_GAP, height),
+    }
+}
+
+/// Top left corner of the current layer of the minefield, in world coordinates of the camera.
+pub fn board_origin(board: &Board, layout: &Layout) -> Point {
+    match board.layers() {
+        1 => Point::new(0, 0),
+        _ => Point::new((layout.field_size().0 / 2 + LAYER_GAP) as i32, 0),
+    }
+}
+
+/// Where the minimap goes in the bottom right corner of the viewport, when the world doesn't fit in it.
+pub fn minimap_rect(camera: &Camera) -> Option<Rect> {
+    if !camera.can_pan() {
+        return None;
+    }
+    let (world_width, world_height) = camera.world();
+    let longest = world_width.max(world_height);
+    let (width, height) = ((world_width * MINIMAP_SIZE / longest).max(1), (world_height * MINIMAP_SIZE / longest).max(1));
+    let viewport = camera.viewport();
+    Some(Rect::new(viewport.right() - width as i32 - 5, viewport.bottom() - height as i32 - 5, width, height))
+}
+
+/// Width of the HUD, and so of the window, above a view of the given width.
+pub fn hud_width(view_width: u32) -> u32 {
+    view_width.max(MIN_HUD_WIDTH)
+}
+
+pub fn face_rect(view_width: u32) -> Rect {
+    let center = Point::new((hud_width(view_width) / 2) as i32, (HUD_HEIGHT / 2) as i32);
+    Rect::from_center(center, FACE_SIZE, FACE_SIZE)
+}
+
+/// Textures for the different looks of a single tile.
+pub struct TileTextures<'a> {
+    pub hidden: Texture<'a>,
+    pub revealed: Texture<'a>,
+    pub flag: Texture<'a>,
+    pub question: Texture<'a>,
+    pub mine: Texture<'a>,
+    pub exploded: Texture<'a>,
+    pub wrong_flag: Texture<'a>,
+}
+impl<'a> TileTextures<'a> {
+    /// Cuts every texture of a square tile set into a hexagon.
+    pub fn to_hex(&self, canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, tile_size: u32) -> Result<Self, String> {
+        Ok(Self {
+            hidden: hex_texture(canvas, texture_creator, tile_size, &self.hidden)?,
+            revealed: hex_texture(canvas, texture_creator, tile_size, &self.revealed)?,
+            flag: hex_texture(canvas, texture_creator, tile_size, &self.flag)?,
+            question: hex_texture(canvas, texture_creator, tile_size, &self.question)?,
+            mine: hex_texture(canvas, texture_creator, tile_size, &self.mine)?,
+            exploded: hex_texture(canvas, texture_creator, tile_size, &self.exploded)?,
+            wrong_flag: hex_texture(canvas, texture_creator, tile_size, &self.wrong_flag)?,
+        })
+    }
+}
+
+/// Textures and colours of a theme used for drawing the minefield.
+pub struct BoardTextures<'a> {
+    pub square: TileTextures<'a>,
+    pub hex: TileTextures<'a>,
+    pub numbers: Vec<Texture<'a>>,
+    pub background: Color,
+    pub hud: Color,
+}
+
+/// Draws all tile and number textures for a theme, at the size the tiles are shown at.
+pub fn board_textures<'a>(canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, theme: &Theme, tile_size: u32) -> Result<BoardTextures<'a>, String> {
+    let (hidden, revealed, flag) = match &theme.tiles {
+        TileArt::Images { hidden, revealed, flag } => (
+            texture_creator.load_texture(asset_path(hidden))?,
+            texture_creator.load_texture(asset_path(revealed))?,
+            texture_creator.load_texture(asset_path(flag))?,
+        ),
+        TileArt::Colors { hidden, revealed, flag, border } => {
+            tile_textures(canvas, texture_creator, tile_size, rgb(*hidden), rgb(*revealed), rgb(*flag), rgb(*border))?
+        },
+    };
+    let question = question_texture(canvas, texture_creator, tile_size, &hidden)?;
+    let (mine, exploded) = mine_textures(canvas, texture_creator, tile_size, &revealed)?;
+    let wrong_flag = wrong_flag_texture(canvas, texture_creator, tile_size, &flag)?;
+    let square = TileTextures {
+        hidden,
+        revealed,
+        flag,
+        question,
+        mine,
+        exploded,
+        wrong_flag,
+    };
+    let hex = square.to_hex(canvas, texture_creator, tile_size)?;
+    let (numbers, _) = number_textures(texture_creator, tile_size, MAX_ADJACENT_BOMBS, &theme.font, |number| theme.number_color(number))?;
+    Ok(BoardTextures {
+        square,
+        hex,
+        numbers,
+        background: rgb(theme.background),
+        hud: rgb(theme.hud),
+    })
+}
+
+/// Draws the tiles of one layer through the camera, shrunk by `scale`. Once the game is over every
+/// mine is uncovered and wrong flags are crossed out.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_layer(
+    canvas: &mut Canvas<Window>,
+    board: &Board,
+    layout: &Layout,
+    textures: &BoardTextures,
+    game_state: GameState,
+    pressed_preview: &[(usize, usize)],
+    layer: u32,
+    origin: Point,
+    scale: u32,
+    camera: &Camera,
+) -> Result<(), String> {
+    let visible = camera.visible();
+    let game_over = matches!(game_state, GameState::GameOver);
+    let won = matches!(game_state, GameState::Won);
+    let tile_textures = match board.topology() {
+        Topology::Square => &textures.square,
+        Topology::Hex => &textures.hex,
+    };
+    let layer_rows = (layer * board.tile_rows) as usize..((layer + 1) * board.tile_rows) as usize;
+    for i in layer_rows {
+        for (j, tile) in board.row(i).enumerate() {
+            if !board.is_playable(i, j) {
+                continue;
+            }
+            let texture = match (tile.state(), tile.value()) {
+                (TileState::Revealed, _) => &tile_textures.revealed,
+                (TileState::Hidden, TileValue::Bomb(_))
+                | (TileState::Questioned, TileValue::Bomb(_)) if game_over => {
+                    match board.exploded() == Some((i, j)) {
+                        true => &tile_textures.exploded,
+                        false => &tile_textures.mine,
+                    }
+                },
+                (TileState::Hidden, TileValue::Bomb(_)) if won => &tile_textures.flag,
+                (TileState::Flagged(flags), _) if game_over && flags != tile.bombs() => &tile_textures.wrong_flag,
+                (TileState::Flagged(_), _) => &tile_textures.flag,
+                (TileState::Questioned, _) => &tile_textures.question,
+                (TileState::Hidden, _) => match pressed_preview.contains(&(i, j)) {
+                    true => &tile_textures.revealed,
+                    false => &tile_textures.hidden,
+                },
+            };
+            let unscaled = layout.tile_rect(Point::new(0, 0), i, j);
+            let world = Rect::new(
+                origin.x() + unscaled.x() / scale as i32,
+                origin.y() + unscaled.y() / scale as i32,
+                unscaled.width() / scale,
+                unscaled.height() / scale,
+            );
+            if !world.has_intersection(visible) {
+                continue;
+            }
+            let rect = camera.to_screen(world);
+            canvas.copy(texture, None, rect)?;
+
+            // numbers go in the middle, counts of flags and bombs in the corner when a tile can hold more than one
+            let multiple = board.options().max_bombs_per_tile() > 1;
+            let (number, shrink) = match (tile.state(), tile.value()) {
+                (TileState::Revealed, TileValue::Adjacent(x)) => (Some(x), 1),
+                (TileState::Flagged(flags), _) if multiple => (Some(flags), 2),
+                (TileState::Hidden, TileValue::Bomb(bombs))
+                | (TileState::Questioned, TileValue::Bomb(bombs)) if multiple && (game_over || won) => (Some(bombs), 2),
+                _ => (None, 1),
+            };
+            if let Some(x) = number {
+                let number_texture = textures.numbers.get(x as usize).ok_or(format!("there is no texture for the number {x}"))?;
+                let query = number_texture.query();
+                let zoomed = |size: u32| ((size as f32 * camera.zoom()) as u32 / scale / shrink).max(1);
+                let (width, height) = (zoomed(query.width), zoomed(query.height));
+                let number_rect = match shrink {
+                    1 => Rect::from_center(rect.center(), width, height),
+                    _ => Rect::new(rect.right() - width as i32, rect.bottom() - height as i32, width, height),
+                };
+                canvas.copy(number_texture, None, number_rect)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Draws the HUD with `counter` on the left, the face in the middle and the timer on the right.
+pub fn draw_hud(
+    canvas: &mut Canvas<Window>,
+    textures: &BoardTextures,
+    game_state: GameState,
+    view_width: u32,
+    counter: &str,
+    face_pressed: bool,
+    seconds: u64,
+) -> Result<(), String> {
+    let width = hud_width(view_width);
+    canvas.set_draw_color(textures.hud);
+    canvas.fill_rect(Rect::new(0, 0, width, HUD_HEIGHT))?;
+    render_text(canvas, HUD_MARGIN as i32, 8, counter)?;
+    let timer_x = (width - HUD_MARGIN - HUD_TEXT_WIDTH) as i32;
+    render_text(canvas, timer_x, 8, format!("{:03}", seconds.min(999)).as_str())?;
+    let face = face_rect(view_width);
+    canvas.copy(
+        if face_pressed { &textures.square.revealed } else { &textures.square.hidden },
+        None,
+        face,
+    )?;
+    let face_text = match game_state {
+        GameState::GameOver => ":(",
+        GameState::Won => "B)",
+        _ => ":)",
+    };
+    render_text(canvas, face.x() + 4, face.y() + 3, face_text)?;
+    Ok(())
+}
+
+/// Draws the HUD and the current layer of the minefield, with the layers next to it ghosted on 3D boards.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_game(
+    canvas: &mut Canvas<Window>,
+    board: &Board,
+    layout: &Layout,
+    textures: &BoardTextures,
+    game_state: GameState,
+    pressed_preview: &[(usize, usize)],
+    face_pressed: bool,
+    seconds: u64,
+    layer: u32,
+    camera: &Camera,
+    cursor: Option<Cursor>,
+) -> Result<(), String> {
+    canvas.set_draw_color(textures.background);
+    canvas.clear();
+
+    let view_width = camera.viewport().width();
+    // a won board shows every bomb flagged, so none are left to find
+    let mines_left = match game_state {
+        GameState::Won => 0,
+        _ => board.bomb_count() as i32 - board.flag_count() as i32,
+    };
+    draw_hud(canvas, textures, game_state, view_width, format!("{:03}", mines_left).as_str(), face_pressed, seconds)?;
+    if board.layers() > 1 {
+        render_text(canvas, 50, 8, format!("L{}/{}", layer + 1, board.layers()).as_str())?;
+    }
+
+    // keep zoomed in tiles from spilling onto the hud
+    canvas.set_clip_rect(camera.viewport());
+    let origin = board_origin(board, layout);
+    draw_layer(canvas, board, layout, textures, game_state, pressed_preview, layer, origin, 1, camera)?;
+
+    // ghost the layer above on the left and the one below on the right
+    let (width, height) = layout.field_size();
+    let ghosts = [
+        (layer.checked_sub(1), Point::new(0, origin.y() + (height / 4) as i32)),
+        (Some(layer + 1), Point::new(origin.x() + (width + LAYER_GAP) as i32, origin.y() + (height / 4) as i32)),
+    ];
+    for (ghost_layer, ghost_origin) in ghosts {
+        match ghost_layer {
+            Some(ghost_layer) if ghost_layer < board.layers() => {
+                draw_layer(canvas, board, layout, textures, game_state, &[], ghost_layer, ghost_origin, 2, camera)?;
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(0, 0, 0, 140));
+                canvas.fill_rect(camera.to_screen(Rect::new(ghost_origin.x(), ghost_origin.y(), width / 2, height / 2)))?;
+                canvas.set_blend_mode(BlendMode::None);
+            },
+            _ => {},
+        };
+    }
+
+    // outline the keyboard cursor twice for a line that stays visible when zoomed out
+    if let Some(cursor) = cursor.filter(|cursor| cursor.layer(board) == layer) {
+        let rect = camera.to_screen(layout.tile_rect(origin, cursor.i, cursor.j));
+        canvas.set_draw_color(Color::RGB(255, 220, 0));
+        canvas.draw_rect(rect)?;
+        if rect.width() > 4 && rect.height() > 4 {
+            canvas.draw_rect(Rect::new(rect.x() + 1, rect.y() + 1, rect.width() - 2, rect.height() - 2))?;
+        }
+    }
+    canvas.set_clip_rect(None);
+
+    if let Some(minimap) = minimap_rect(camera) {
+        draw_minimap(canvas, board, layout, game_state, layer, camera, minimap)?;
+    }
+    Ok(())
+}
+
+/// Draws the current layer with one colour per tile state, and outlines the part the camera shows.
+pub fn draw_minimap(
+    canvas: &mut Canvas<Window>,
+    board: &Board,
+    layout: &Layout,
+    game_state: GameState,
+    layer: u32,
+    camera: &Camera,
+    minimap: Rect,
+) -> Result<(), String> {
+    let (world_width, world_height) = camera.world();
+    let to_minimap = |world: Rect| {
+        let left = minimap.x() + world.left() * minimap.width() as i32 / world_width as i32;
+        let top = minimap.y() + world.top() * minimap.height() as i32 / world_height as i32;
+        let right = minimap.x() + world.right() * minimap.width() as i32 / world_width as i32;
+        let bottom = minimap.y() + world.bottom() * minimap.height() as i32 / world_height as i32;
+        Rect::new(left, top, (right - left).max(1) as u32, (bottom - top).max(1) as u32)
+    };
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.fill_rect(minimap)?;
+
+    // group the tiles by colour so each colour is a single draw call
+    let game_over = matches!(game_state, GameState::GameOver);
+    let (mut hidden, mut revealed, mut flagged, mut mines) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let origin = board_origin(board, layout);
+    let layer_rows = (layer * board.tile_rows) as usize..((layer + 1) * board.tile_rows) as usize;
+    for i in layer_rows {
+        for (j, tile) in board.row(i).enumerate() {
+            if !board.is_playable(i, j) {
+                continue;
+            }
+            let rect = to_minimap(layout.tile_rect(origin, i, j));
+            match tile.state() {
+                TileState::Revealed => revealed.push(rect),
+                TileState::Flagged(_) => flagged.push(rect),
+                _ if game_over && tile.is_bomb() => mines.push(rect),
+                _ => hidden.push(rect),
+            };
+        }
+    }
+    for (rects, color) in [
+        (hidden, Color::RGB(120, 120, 120)),
+        (revealed, Color::RGB(210, 210, 210)),
+        (flagged, Color::RGB(220, 60, 60)),
+        (mines, Color::RGB(20, 20, 20)),
+    ] {
+        canvas.set_draw_color(color);
+        canvas.fill_rects(&rects)?;
+    }
+    canvas.set_draw_color(Color::RGB(255, 220, 0));
+    canvas.draw_rect(to_minimap(camera.visible()).intersection(minimap).unwrap_or(minimap))?;
+    Ok(())
+}
+
+/// Draws the part of an endless field seen by the camera, with the score in the HUD.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_endless(
+    canvas: &mut Canvas<Window>,
+    field: &EndlessBoard,
+    textures: &BoardTextures,
+    game_state: GameState,
+    pressed_preview: &[(i64, i64)],
+    face_pressed: bool,
+    seconds: u64,
+    camera: (i64, i64),
+    view: (u32, u32),
+    tile_size: u32,
+) -> Result<(), String> {
+    canvas.set_draw_color(textures.background);
+    canvas.clear();
+
+    let game_over = matches!(game_state, GameState::GameOver);
+    let (size, tile_size) = (tile_size, tile_size as i64);
+    let (left, top) = (camera.0.div_euclid(tile_size), camera.1.div_euclid(tile_size));
+    let (right, bottom) = ((camera.0 + view.0 as i64).div_euclid(tile_size), (camera.1 + view.1 as i64).div_euclid(tile_size));
+    for y in top..=bottom {
+        for x in left..=right {
+            let texture = match field.state(x, y) {
+                TileState::Revealed => &textures.square.revealed,
+                TileState::Hidden
+                | TileState::Questioned if game_over && field.is_bomb(x, y) => {
+                    match field.exploded() == Some((x, y)) {
+                        true => &textures.square.exploded,
+                        false => &textures.square.mine,
+                    }
+                },
+                TileState::Flagged(_) if game_over && !field.is_bomb(x, y) => &textures.square.wrong_flag,
+                TileState::Flagged(_) => &textures.square.flag,
+                TileState::Questioned => &textures.square.question,
+                TileState::Hidden => match pressed_preview.contains(&(x, y)) {
+                    true => &textures.square.revealed,
+                    false => &textures.square.hidden,
+                },
+            };
+            let rect = Rect::new(
+                (x * tile_size - camera.0) as i32,
+                (y * tile_size - camera.1) as i32 + HUD_HEIGHT as i32,
+                size,
+                size,
+            );
+            canvas.copy(texture, None, rect)?;
+            if let TileState::Revealed = field.state(x, y) {
+                let number_texture = &textures.numbers[field.adjacent(x, y) as usize];
+                let query = number_texture.query();
+                canvas.copy(number_texture, None, Rect::from_center(rect.center(), query.width, query.height))?;
+            }
+        }
+    }
+
+    // the hud goes on top of the tiles that are partly scrolled under it
+    draw_hud(canvas, textures, game_state, view.0, format!("{:05}", field.score()).as_str(), face_pressed, seconds)?;
+    Ok(())
+}
+
@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use sdl2::pixels::Color;
+use serde::Deserialize;
+use crate::minesweeper::error::AssetError;
+
+/// How the hidden, revealed and flagged tiles look.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileArt {
+    /// Paths to square images for each tile.
+    Images {
+        hidden: String,
+        revealed: String,
+        flag: String,
+    },
+    /// Flat tiles drawn in the given colours, hidden tiles get a border.
+    Colors {
+        hidden: [u8; 3],
+        revealed: [u8; 3],
+        flag: [u8; 3],
+        border: [u8; 3],
+    },
+}
+
+/// Everything that decides the look of the minefield. Themes are read from JSON files in the
+/// format of the bundled ones in `assets/themes`.
+#[derive(Clone, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub tiles: TileArt,
+    pub background: [u8; 3],
+    pub hud: [u8; 3],
+    /// Colours of the numbers starting at 0, larger numbers take the last colour.
+    pub numbers: Vec<[u8; 3]>,
+    /// Font the numbers are written in.
+    pub font: String,
+}
+
+impl Theme {
+    pub fn from_json(json: &str) -> Result<Self, AssetError> {
+        let theme: Theme = serde_json::from_str(json).map_err(|e| AssetError::Theme(e.to_string()))?;
+        if theme.numbers.is_empty() {
+            return Err(AssetError::Theme(format!("theme {} has no number colours", theme.name)));
+        }
+        Ok(theme)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AssetError> {
+        let json = std::fs::read_to_string(path).map_err(|e| AssetError::Read(path.to_path_buf(), e))?;
+        Self::from_json(&json).map_err(|e| AssetError::Theme(format!("{}: {e}", path.display())))
+    }
+
+    pub fn number_color(&self, number: u32) -> Color {
+        rgb(self.numbers[(number as usize).min(self.numbers.len() - 1)])
+    }
+}
+
+pub fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::RGB(r, g, b)
+}
+
+/// The themes that come with the game: classic, dark and a high contrast one with numbers in a
+/// palette that stays apart for colour blind players.
+pub fn builtin_themes() -> Vec<Theme> {
+    [
+        include_str!("../../assets/themes/classic.json"),
+        include_str!("../../assets/themes/dark.json"),
+        include_str!("../../assets/themes/high_contrast.json"),
+    ]
+    .iter()
+    .map(|json| Theme::from_json(json).expect("bundled themes are valid"))
+    .collect()
+}
+
+/// Where user themes are looked for: `minesweeper/themes` in the platform's config directory.
+pub fn user_theme_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("minesweeper").join("themes"))
+}
+
+/// Reads every `.json` file in the user theme directory, sorted by file name.
+pub fn user_themes() -> Vec<Result<Theme, AssetError>> {
+    let Some(entries) = user_theme_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| Theme::load(path))
+        .collect()
+}
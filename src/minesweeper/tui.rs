@@ -0,0 +1,440 @@
+use std::io::{self, Stdout, Write};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self,
+        DisableMouseCapture,
+        EnableMouseCapture,
+        Event,
+        KeyCode,
+        KeyEventKind,
+        KeyModifiers,
+        MouseButton,
+        MouseEventKind,
+    },
+    execute,
+    queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use game_clones::minesweeper::board::{
+    Action,
+    Board,
+    BoardOptions,
+    ChordMismatch,
+    GameState,
+    Preset,
+    TileState,
+    TileValue,
+    Topology,
+    validate_settings,
+};
+use game_clones::minesweeper::cursor::{Cursor, CursorAction, PAGE_STEP};
+use game_clones::minesweeper::net::{Connection, SERVER_ADDRESS};
+
+/// Columns taken up by a tile. Odd hex rows are shifted by half of that.
+const TILE_WIDTH: usize = 2;
+/// Lines above the board, holding the status line.
+const HEADER: u16 = 1;
+/// Lines below the board, holding the key help.
+const FOOTER: u16 = 1;
+/// Colours of the numbers from 1 up, as in the classic game. Larger numbers take the last one.
+const NUMBER_COLORS: [Color; 8] = [
+    Color::Blue,
+    Color::DarkGreen,
+    Color::Red,
+    Color::DarkBlue,
+    Color::DarkRed,
+    Color::DarkCyan,
+    Color::Magenta,
+    Color::Grey,
+];
+const USAGE: &str = "usage: minesweeper-tui [--preset beginner|intermediate|expert] [--wrap] [--no-mouse] [--hex | --connect [ADDRESS]]";
+
+struct Settings {
+    preset: Preset,
+    options: BoardOptions,
+    /// Address of the server to play against, a local game is played without one.
+    server: Option<String>,
+    mouse: bool,
+    colors: bool,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Settings, String> {
+    let mut settings = Settings {
+        preset: Preset::Beginner,
+        options: BoardOptions::default(),
+        server: None,
+        mouse: true,
+        // see https://no-color.org
+        colors: std::env::var_os("NO_COLOR").is_none() && std::env::var("TERM").map_or(true, |term| term != "dumb"),
+    };
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--preset" => {
+                let name = args.next().ok_or(USAGE)?;
+                settings.preset = *Preset::ALL
+                    .iter()
+                    .find(|preset| preset.name().eq_ignore_ascii_case(&name))
+                    .ok_or(format!("unknown preset {name}"))?;
+            },
+            "--hex" => settings.options.topology = Topology::Hex,
+            "--wrap" => settings.options.wrap = true,
+            "--no-mouse" => settings.mouse = false,
+            // the address is optional, so the next argument may already be another flag
+            "--connect" => settings.server = Some(
+                args.next_if(|arg| !arg.starts_with("--")).unwrap_or(SERVER_ADDRESS.to_string())
+            ),
+            _ => return Err(USAGE.to_string()),
+        };
+    }
+    // the server only deals out square boards
+    if settings.server.is_some() && settings.options.topology == Topology::Hex {
+        return Err("--hex can't be played with --connect, the server only has square boards".to_string());
+    }
+    Ok(settings)
+}
+
+/// Puts the terminal into raw mode on the alternate screen, and restores it when dropped so that
+/// an error doesn't leave the shell unusable.
+struct Terminal {
+    out: Stdout,
+    mouse: bool,
+}
+
+impl Terminal {
+    fn new(mouse: bool) -> Result<Self, String> {
+        terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+        let mut terminal = Self { out: io::stdout(), mouse: false };
+        execute!(terminal.out, EnterAlternateScreen, Hide).map_err(|e| e.to_string())?;
+        // not every terminal reports the mouse, the keyboard works either way
+        terminal.mouse = mouse && execute!(terminal.out, EnableMouseCapture).is_ok();
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.mouse {
+            let _ = execute!(self.out, DisableMouseCapture);
+        }
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Where the moves go: straight onto the board, or past the server first.
+enum Mode {
+    Local,
+    Remote(Connection),
+}
+
+struct Game {
+    board: Board,
+    mode: Mode,
+    cursor: Cursor,
+    /// Row and column of the top left tile on screen, for boards larger than the terminal.
+    scroll: (usize, usize),
+    /// Tile the left mouse button went down on.
+    pressed: Option<(usize, usize)>,
+    message: Option<String>,
+    colors: bool,
+}
+
+impl Game {
+    fn new(board: Board, mode: Mode, colors: bool) -> Self {
+        Self {
+            cursor: Cursor::new(&board, 0),
+            board,
+            mode,
+            scroll: (0, 0),
+            pressed: None,
+            message: None,
+            colors,
+        }
+    }
+
+    /// Carries out a move on `(i, j)`, asking the server first when playing remotely.
+    fn play(&mut self, action: CursorAction, i: usize, j: usize) -> Result<(), String> {
+        if let Mode::Remote(connection) = &self.mode {
+            // a chord is a reveal of an already revealed tile to the server, and the board has to
+            // make the move the way the server does or the two copies drift apart
+            let request = match action {
+                CursorAction::Reveal | CursorAction::Chord => Action::Reveal(i as u32, j as u32),
+                CursorAction::Flag => Action::ToggleFlag(i as u32, j as u32),
+            };
+            if !connection.play(&mut self.board, request)? {
+                self.message = Some("The server refused that move".to_string());
+            }
+            return Ok(());
+        }
+        match action {
+            CursorAction::Reveal => {
+                self.board.protect_first_click(i, j);
+                self.board.resolve_click(i, j);
+            },
+            CursorAction::Flag => self.board.resolve_flag(i, j),
+            CursorAction::Chord => self.board.chord(i, j, ChordMismatch::Ignore),
+        };
+        Ok(())
+    }
+
+    /// Tells the server the game is over. A local game just ends.
+    fn quit(&self) -> Result<(), String> {
+        if let Mode::Remote(connection) = &self.mode {
+            match self.board.state() {
+                GameState::Won => {
+                    connection.validate(Action::Won)?;
+                },
+                _ => connection.quit()?,
+            }
+        }
+        Ok(())
+    }
+
+    fn restart(&mut self) -> Result<(), String> {
+        if let Mode::Local = self.mode {
+            self.board = self.board.restart()?;
+            self.cursor = Cursor::new(&self.board, 0);
+            self.message = None;
+        }
+        Ok(())
+    }
+
+    /// Rows and columns of tiles that fit into a terminal of the given size.
+    fn visible_tiles(&self, (width, height): (u16, u16)) -> (usize, usize) {
+        let rows = height.saturating_sub(HEADER + FOOTER) as usize;
+        // leave room for the shift of odd hex rows
+        let columns = (width as usize).saturating_sub(1) / TILE_WIDTH;
+        (rows.max(1), columns.max(1))
+    }
+
+    /// Scrolls just far enough for the cursor to be on screen.
+    fn follow_cursor(&mut self, size: (u16, u16)) {
+        let (rows, columns) = self.visible_tiles(size);
+        let i = self.cursor.i % self.board.tile_rows as usize;
+        let (top, left) = &mut self.scroll;
+        *top = (*top).clamp(i.saturating_sub(rows - 1), i);
+        *left = (*left).clamp(self.cursor.j.saturating_sub(columns - 1), self.cursor.j);
+    }
+
+    /// Returns the tile of the cursor's layer drawn at a terminal position.
+    fn tile_at(&self, column: u16, row: u16) -> Option<(usize, usize)> {
+        let rows = self.board.tile_rows as usize;
+        let i = self.scroll.0 + row.checked_sub(HEADER)? as usize;
+        if i >= rows {
+            return None;
+        }
+        let column = (column as usize).checked_sub(self.row_shift(i))?;
+        let j = self.scroll.1 + column / TILE_WIDTH;
+        let i = self.cursor.layer(&self.board) as usize * rows + i;
+        match j < self.board.tile_columns as usize && self.board.is_playable(i, j) {
+            true => Some((i, j)),
+            false => None,
+        }
+    }
+
+    /// Columns the given row of a layer is shifted to the right.
+    fn row_shift(&self, i: usize) -> usize {
+        match self.board.topology() {
+            Topology::Hex if i % 2 == 1 => TILE_WIDTH / 2,
+            _ => 0,
+        }
+    }
+
+    /// Handles a key press. Returns whether to keep playing.
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<bool, String> {
+        let step = match modifiers.contains(KeyModifiers::CONTROL) {
+            true => PAGE_STEP as i64,
+            false => 1,
+        };
+        let board = &self.board;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(false),
+            KeyCode::Char('r') => {
+                self.restart()?;
+                return Ok(true);
+            },
+            KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h') => self.cursor.shift(board, 0, -step),
+            KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l') => self.cursor.shift(board, 0, step),
+            KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k') => self.cursor.shift(board, -step, 0),
+            KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j') => self.cursor.shift(board, step, 0),
+            KeyCode::Home => self.cursor.j = 0,
+            KeyCode::End => self.cursor.j = board.tile_columns as usize - 1,
+            KeyCode::Tab => self.cursor.next_edge(board, true),
+            KeyCode::BackTab => self.cursor.next_edge(board, false),
+            KeyCode::PageUp => {
+                let layer = self.cursor.layer(board).saturating_sub(1);
+                self.cursor.set_layer(board, layer);
+            },
+            KeyCode::PageDown => {
+                let layer = (self.cursor.layer(board) + 1).min(board.layers() - 1);
+                self.cursor.set_layer(board, layer);
+            },
+            KeyCode::Char(' ') | KeyCode::Enter => return self.play(CursorAction::Reveal, self.cursor.i, self.cursor.j).map(|_| true),
+            KeyCode::Char('f') => return self.play(CursorAction::Flag, self.cursor.i, self.cursor.j).map(|_| true),
+            KeyCode::Char('c') => return self.play(CursorAction::Chord, self.cursor.i, self.cursor.j).map(|_| true),
+            _ => {},
+        };
+        Ok(true)
+    }
+
+    /// Handles a mouse button going down or up over the terminal position `(column, row)`.
+    fn mouse(&mut self, kind: MouseEventKind, column: u16, row: u16) -> Result<(), String> {
+        let tile = self.tile_at(column, row);
+        if let (Some((i, j)), MouseEventKind::Down(_)) = (tile, kind) {
+            (self.cursor.i, self.cursor.j) = (i, j);
+        }
+        match (kind, tile) {
+            (MouseEventKind::Down(MouseButton::Left), _) => self.pressed = tile,
+            // like a button, a tile is only revealed when the mouse is released over it
+            (MouseEventKind::Up(MouseButton::Left), Some((i, j))) if self.pressed == tile => {
+                self.pressed = None;
+                self.play(CursorAction::Reveal, i, j)?;
+            },
+            (MouseEventKind::Down(MouseButton::Right), Some((i, j))) => self.play(CursorAction::Flag, i, j)?,
+            (MouseEventKind::Down(MouseButton::Middle), Some((i, j))) => self.play(CursorAction::Chord, i, j)?,
+            _ => {},
+        };
+        Ok(())
+    }
+
+    fn status(&self) -> String {
+        let bombs_left = self.board.bomb_count() as i64 - self.board.flag_count() as i64;
+        let mut status = format!("Bombs left: {bombs_left}");
+        if self.board.layers() > 1 {
+            status += &format!("  Layer {}/{}", self.cursor.layer(&self.board) + 1, self.board.layers());
+        }
+        match self.board.state() {
+            GameState::InGame => {},
+            GameState::Won => status += "  You won!",
+            GameState::GameOver => status += "  Game over!",
+        };
+        if let Some(message) = &self.message {
+            status += &format!("  {message}");
+        }
+        status
+    }
+
+    fn help(&self) -> &'static str {
+        match self.mode {
+            Mode::Local => "arrows/hjkl move  space reveal  f flag  c chord  tab next  r restart  q quit",
+            Mode::Remote(_) => "arrows/hjkl move  space reveal  f flag  c chord  tab next  q quit",
+        }
+    }
+
+    /// Text and colours of a tile. Once the game is lost all bombs are shown, as well as the flags that were wrong.
+    fn tile_look(&self, i: usize, j: usize) -> (String, Option<Color>, Option<Color>) {
+        let tile = self.board.tile(i, j);
+        let lost = self.board.state() == GameState::GameOver;
+        match (tile.state(), tile.value()) {
+            (_, TileValue::Bomb(_)) if self.board.exploded() == Some((i, j)) => ("* ".to_string(), Some(Color::Black), Some(Color::Red)),
+            (TileState::Hidden | TileState::Questioned, TileValue::Bomb(_)) if lost => ("* ".to_string(), Some(Color::Black), Some(Color::DarkGrey)),
+            (TileState::Flagged(_), TileValue::Adjacent(_)) if lost => ("X ".to_string(), Some(Color::Red), Some(Color::DarkGrey)),
+            (TileState::Flagged(1), _) => ("F ".to_string(), Some(Color::Red), Some(Color::DarkGrey)),
+            (TileState::Flagged(count), _) => (format!("F{count}"), Some(Color::Red), Some(Color::DarkGrey)),
+            (TileState::Questioned, _) => ("? ".to_string(), Some(Color::White), Some(Color::DarkGrey)),
+            (TileState::Hidden, _) => (". ".to_string(), Some(Color::Grey), Some(Color::DarkGrey)),
+            (TileState::Revealed, TileValue::Adjacent(0)) => ("  ".to_string(), None, None),
+            (TileState::Revealed, TileValue::Adjacent(x)) => {
+                let color = NUMBER_COLORS[(x as usize - 1).min(NUMBER_COLORS.len() - 1)];
+                (format!("{x:<2}"), Some(color), None)
+            },
+            (TileState::Revealed, TileValue::Bomb(_)) => ("* ".to_string(), Some(Color::Red), None),
+        }
+    }
+
+    /// Prints `text`, in the given colours unless they are turned off.
+    fn paint(&self, out: &mut impl Write, text: &str, fg: Option<Color>, bg: Option<Color>) -> io::Result<()> {
+        if self.colors {
+            if let Some(fg) = fg {
+                queue!(out, SetForegroundColor(fg))?;
+            }
+            if let Some(bg) = bg {
+                queue!(out, SetBackgroundColor(bg))?;
+            }
+        }
+        queue!(out, Print(text), ResetColor)
+    }
+
+    /// Draws the status line, the cursor's layer and the key help into a terminal of the given size.
+    fn draw(&self, out: &mut impl Write, size: (u16, u16)) -> io::Result<()> {
+        let (rows, columns) = self.visible_tiles(size);
+        let layer_rows = self.board.tile_rows as usize;
+        let layer_start = self.cursor.layer(&self.board) as usize * layer_rows;
+
+        queue!(out, MoveTo(0, 0), Clear(ClearType::CurrentLine))?;
+        self.paint(out, &fit(&self.status(), size.0), None, None)?;
+
+        let last_row = (self.scroll.0 + rows).min(layer_rows);
+        let last_column = (self.scroll.1 + columns).min(self.board.tile_columns as usize);
+        for (line, li) in (self.scroll.0..last_row).enumerate() {
+            let i = layer_start + li;
+            queue!(out, MoveTo(0, HEADER + line as u16), Clear(ClearType::CurrentLine))?;
+            queue!(out, Print(" ".repeat(self.row_shift(li))))?;
+            for j in self.scroll.1..last_column {
+                if !self.board.is_playable(i, j) {
+                    queue!(out, Print(" ".repeat(TILE_WIDTH)))?;
+                    continue;
+                }
+                let (text, fg, bg) = self.tile_look(i, j);
+                if (i, j) == (self.cursor.i, self.cursor.j) {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                    // without colours the reversed tile is the only thing marking the cursor
+                    self.paint(out, &text, fg, bg)?;
+                    queue!(out, SetAttribute(Attribute::Reset))?;
+                } else {
+                    self.paint(out, &text, fg, bg)?;
+                }
+            }
+        }
+        queue!(out, MoveTo(0, HEADER + (last_row - self.scroll.0) as u16), Clear(ClearType::FromCursorDown))?;
+        queue!(out, MoveTo(0, size.1.saturating_sub(1)))?;
+        self.paint(out, &fit(self.help(), size.0), Some(Color::DarkGrey), None)?;
+        out.flush()
+    }
+}
+
+/// Cuts `text` down to fit a line of the given width. The last column is left empty, as writing
+/// to the bottom right corner scrolls some terminals.
+fn fit(text: &str, width: u16) -> String {
+    text.chars().take((width as usize).saturating_sub(1)).collect()
+}
+
+fn main() -> Result<(), String> {
+    let settings = parse_args(std::env::args().skip(1))?;
+    let (columns, rows, bombs) = settings.preset.settings();
+    let (board, mode) = match &settings.server {
+        Some(address) => {
+            let (connection, board) = Connection::start(address.as_str(), rows, columns, bombs, settings.options.wrap)?;
+            (board, Mode::Remote(connection))
+        },
+        None => {
+            let (columns, rows, bombs) = validate_settings(Some(columns), Some(rows), Some(bombs), settings.options)?;
+            (Board::with_options(rows, columns, bombs, settings.options)?, Mode::Local)
+        },
+    };
+    let mut game = Game::new(board, mode, settings.colors);
+
+    let mut terminal = Terminal::new(settings.mouse)?;
+    loop {
+        let size = terminal::size().map_err(|e| e.to_string())?;
+        game.follow_cursor(size);
+        game.draw(&mut terminal.out, size).map_err(|e| e.to_string())?;
+
+        // nothing moves on its own, so the screen only changes after an event
+        match event::read().map_err(|e| e.to_string())? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                game.message = None;
+                if !game.key(key.code, key.modifiers)? {
+                    break;
+                }
+            },
+            Event::Mouse(mouse) => game.mouse(mouse.kind, mouse.column, mouse.row)?,
+            _ => {},
+        };
+    }
+    game.quit()
+}
@@ -0,0 +1,296 @@
+use core::slice::Iter;
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+};
+
+pub const WINDOW_WIDTH: u32 = 500;
+pub const WINDOW_HEIGHT: u32 = 500;
+
+pub const PLAYER_WIDTH: u32 = 50;
+pub const PLAYER_HEIGHT: u32 = 20;
+pub const PLAYER_MOVE_SPEED: i32 = 1;
+
+pub const BULLET_WIDTH: u32 = 3;
+pub const BULLET_HEIGHT: u32 = 10;
+pub const BULLET_SPEED: i32 = 3;
+
+pub const ENEMY_HEALTH: u8 = 2;
+pub const ENEMY_WIDTH: u32 = 15;
+pub const ENEMY_HEIGHT: u32 = 10;
+pub const ENEMY_MOVE_SPEED: i32 = 1;
+pub const ENEMY_DISTANT: i32 = 10;
+
+
+pub enum GameState {
+    Running,
+    GameOver,
+}
+
+pub struct Enemy {
+    rect: Rect,
+    health: u8,
+    dir: i32,
+}
+impl Enemy {
+    fn new() -> Enemy {
+        Enemy {
+            rect: Rect::new(0, 0, ENEMY_WIDTH, ENEMY_HEIGHT),
+            health: ENEMY_HEALTH,
+            dir: 1,
+        }
+    }
+    fn impact(&mut self, bullet: &Rect) -> bool {
+        if !self.rect.has_intersection(*bullet) {
+            return false;
+        }
+        self.health -= 1;
+        true
+    }
+    fn is_dead(&self) -> bool {
+        self.health == 0
+    }
+}
+
+pub struct SpaceInvaders {
+    pub player_rect: Rect,
+    pub bullets: Vec<Rect>,
+    enemies: Vec<Enemy>,
+    enemies_to_spawn: u32,
+    pub state: GameState,
+}
+impl SpaceInvaders {
+    pub fn new(enemy_count: u32) -> Self {
+        Self {
+            // centered at the bottom of the window
+            player_rect: Rect::new(
+                (WINDOW_WIDTH / 2 - PLAYER_WIDTH / 2) as i32,
+                (WINDOW_HEIGHT - PLAYER_HEIGHT) as i32,
+                PLAYER_WIDTH,
+                PLAYER_HEIGHT,
+            ),
+            bullets: Vec::new(),
+            enemies: vec![Enemy::new()],
+            enemies_to_spawn: enemy_count - 1,
+            state: GameState::Running,
+        }
+    }
+    pub fn enemies(&self) -> Iter<'_, Enemy> {
+        self.enemies.iter()
+    }
+    pub fn update_player(&mut self, left: i32, right: i32) {
+        self.player_rect.set_x(self.player_rect.x() + (right - left) * PLAYER_MOVE_SPEED);
+        if self.player_rect.x() + PLAYER_WIDTH as i32 > WINDOW_WIDTH as i32{
+            self.player_rect.set_x((WINDOW_WIDTH - PLAYER_WIDTH) as i32);
+        }
+        if self.player_rect.x() < 0 {
+            self.player_rect.set_x(0);
+        }
+    }
+    pub fn update(&mut self) {
+        self.update_enemies();
+        self.update_bullets();
+        self.check_hits();
+        self.maybe_spawn_enemy();
+    }
+    fn update_enemies(&mut self) {
+        for enemy in self.enemies.iter_mut() {
+            enemy.rect.set_x(enemy.rect.x + enemy.dir * ENEMY_MOVE_SPEED);
+            if enemy.rect.x + ENEMY_WIDTH as i32 > WINDOW_WIDTH as i32 {
+                enemy.rect.set_x((WINDOW_WIDTH - ENEMY_WIDTH) as i32);
+                enemy.rect.set_y(enemy.rect.y + 5 + ENEMY_HEIGHT as i32);
+                enemy.dir = -1;
+            }
+            if enemy.rect.x < 0 {
+                enemy.rect.set_x(0);
+                enemy.rect.set_y(enemy.rect.y + 5 + ENEMY_HEIGHT as i32);
+                enemy.dir = 1;
+            }
+        }
+    }
+    fn update_bullets(&mut self) {
+        let mut i = 0;
+        while i < self.bullets.len() {
+            let bullet = self.bullets.get_mut(i).unwrap();
+            bullet.set_y(bullet.y - BULLET_SPEED);
+            if bullet.y + (BULLET_HEIGHT as i32) < 0 {
+                self.bullets.remove(i);
+                continue;
+            }
+            i += 1;
+        }
+    }
+    pub fn shoot(&mut self) {
+        self.bullets.push(Rect::new(
+            self.player_rect.x + PLAYER_WIDTH as i32 / 2 - BULLET_WIDTH as i32,
+            self.player_rect.y,
+            BULLET_WIDTH,
+            BULLET_HEIGHT
+        ));
+    }
+    fn check_hits(&mut self) {
+        let mut i = 0;
+        while i < self.bullets.len() {
+            let bullet = self.bullets.get(i).unwrap();
+            for j in 0..self.enemies.len() {
+                let enemy = self.enemies.get_mut(j).unwrap();
+                if enemy.impact(bullet) {
+                    if enemy.is_dead() {
+                        self.enemies.remove(j);
+                    }
+                    self.bullets.remove(i);
+                    break;
+                }
+            }
+            i += 1;
+        }
+    }
+    fn maybe_spawn_enemy(&mut self) {
+        if self.enemies_to_spawn == 0 {
+            return;
+        }
+        match self.enemies.last() {
+            Some(enemy) => {
+                if enemy.rect.x > (ENEMY_WIDTH as i32) + ENEMY_DISTANT || enemy.rect.y > ENEMY_HEIGHT as i32 {
+                    self.enemies.push(Enemy::new());
+                    self.enemies_to_spawn -= 1;
+                }
+            },
+            None => {
+                self.enemies.push(Enemy::new());
+                self.enemies_to_spawn -= 1;
+            },
+        }
+    }
+}
+
+pub fn enemy_textures<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>) -> Result<Vec<Texture<'a>>, String> {
+    let mut textures = Vec::new();
+    for _ in 0..ENEMY_HEALTH {
+        textures.push(
+            texture_creator
+                .create_texture_target(None, ENEMY_WIDTH, ENEMY_HEIGHT)
+                .map_err(|e| e.to_string())?
+        );
+    }
+    let mut texture_refs = Vec::new();
+    for (i, texture_ref) in textures.iter_mut().enumerate() {
+        texture_refs.push((texture_ref, i as u8));
+    }
+    let distance = 200 / (ENEMY_HEALTH - 1);
+
+    canvas
+        .with_multiple_texture_canvas(texture_refs.iter(), |texture_canvas, user_context| {
+            texture_canvas.set_draw_color(Color::RGB(0, 0, 0));
+            texture_canvas.clear();
+            texture_canvas.set_draw_color(Color::RGB(200 - distance * user_context, distance * user_context, 0));
+            texture_canvas
+                .fill_rect(Rect::new(0, 0, ENEMY_WIDTH, ENEMY_HEIGHT))
+                .expect("could not draw point");
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(textures)
+}
+
+pub fn dummy_texture<'a> (canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>) -> Result<(Texture<'a>, Texture<'a>, Texture<'a>), String> {
+    enum TextureKind {
+        Enemy,
+        Player,
+        Bullet,
+    }
+    let mut enemy_texture = texture_creator
+        .create_texture_target(None, ENEMY_WIDTH, ENEMY_HEIGHT)
+        .map_err(|e| e.to_string())?;
+    let mut player_texture = texture_creator
+        .create_texture_target(None, PLAYER_WIDTH, PLAYER_HEIGHT)
+        .map_err(|e| e.to_string())?;
+    let mut bullet_texture = texture_creator
+        .create_texture_target(None, BULLET_WIDTH, BULLET_HEIGHT)
+        .map_err(|e| e.to_string())?;
+
+    let textures = [
+        (&mut enemy_texture, TextureKind::Enemy),
+        (&mut player_texture, TextureKind::Player),
+        (&mut bullet_texture, TextureKind::Bullet),
+    ];
+    canvas
+        .with_multiple_texture_canvas(textures.iter(), |texture_canvas, user_context| {
+            texture_canvas.set_draw_color(Color::RGB(0, 0, 0));
+            texture_canvas.clear();
+            match user_context {
+                TextureKind::Enemy => {
+                    texture_canvas.set_draw_color(Color::RGB(200, 0, 100));
+                    texture_canvas
+                    .fill_rect(Rect::new(0, 0, ENEMY_WIDTH, ENEMY_HEIGHT))
+                    .expect("could not draw point");
+                },
+                TextureKind::Player => {
+                    // drawn in the texture's own coordinates, not where the player is
+                    let player = Rect::new(0, 0, PLAYER_WIDTH, PLAYER_HEIGHT);
+                    let mut ship = player;
+                    ship.set_height(ship.height() - 5);
+                    ship.set_y(ship.y() + 5);
+                    let barrel = Rect::from_center(player.center(), 5, PLAYER_HEIGHT);
+                    texture_canvas.set_draw_color(Color::RGB(100, 0, 200));
+                    texture_canvas
+                    .fill_rect(ship)
+                    .expect("could not draw point");
+                    texture_canvas
+                    .fill_rect(barrel)
+                    .expect("could not draw point");
+                },
+                TextureKind::Bullet => {
+                    texture_canvas.set_draw_color(Color::RGB(200, 200, 200));
+                    texture_canvas
+                    .fill_rect(Rect::new(0, 0, BULLET_WIDTH, BULLET_HEIGHT))
+                    .expect("could not draw point");
+                },
+            };
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok((enemy_texture, player_texture, bullet_texture))
+}
+
+/// Everything the game is drawn with.
+pub struct Textures<'a> {
+    /// One per health point an enemy has left, starting at 1.
+    pub enemies: Vec<Texture<'a>>,
+    pub player: Texture<'a>,
+    pub bullet: Texture<'a>,
+}
+
+pub fn textures<'a>(canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>) -> Result<Textures<'a>, String> {
+    let (_, player, bullet) = dummy_texture(canvas, texture_creator)?;
+    let enemies = enemy_textures(canvas, texture_creator)?;
+    Ok(Textures { enemies, player, bullet })
+}
+
+/// Draws a frame of the game, without presenting it.
+pub fn draw(canvas: &mut Canvas<Window>, game: &SpaceInvaders, textures: &Textures) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    for enemy in game.enemies() {
+        canvas.copy(
+            textures.enemies.get((enemy.health - 1) as usize).unwrap(),
+            None,
+            enemy.rect,
+        )?;
+    }
+    for bullet in game.bullets.iter() {
+        canvas.copy(
+            &textures.bullet,
+            None,
+            *bullet,
+        )?;
+    }
+    canvas.copy(
+        &textures.player,
+        None,
+        game.player_rect,
+    )?;
+    Ok(())
+}
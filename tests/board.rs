@@ -0,0 +1,514 @@
+//! Tests of the minesweeper engine: placing bombs, revealing, chording, winning and losing,
+//! and sending boards over the wire.
+
+use std::collections::{BTreeSet, VecDeque};
+use proptest::prelude::*;
+use game_clones::minesweeper::board::{
+    build_minefield,
+    Action,
+    Board,
+    BoardOptions,
+    ChordMismatch,
+    GameState,
+    TileState,
+    TileValue,
+    Topology,
+};
+use game_clones::minesweeper::cursor::Cursor;
+use game_clones::minesweeper::error::EngineError;
+use game_clones::minesweeper::mask::Mask;
+
+fn options() -> impl Strategy<Value = BoardOptions> {
+    (prop_oneof![Just(Topology::Square), Just(Topology::Hex)], any::<bool>(), 1..=3u32, 1..=3u32)
+        .prop_map(|(topology, wrap, layers, bombs_per_tile)| BoardOptions { topology, wrap, layers, bombs_per_tile })
+}
+
+/// A board of any size down to a single tile, so 1×N and N×1 boards come up often.
+fn board() -> impl Strategy<Value = Board> {
+    (1..=12u32, 1..=12u32, 0..=60u32, options(), any::<u64>())
+        // the rows of a wrapping hex grid only line up in pairs
+        .prop_filter("wrapping hex grids need an even number of rows", |(rows, _, _, options, _)| {
+            !(options.topology == Topology::Hex && options.wrap && rows % 2 == 1)
+        })
+        .prop_map(|(rows, columns, bombs, options, seed)| {
+            // leave at least one safe tile to click on
+            let bombs = bombs.min((rows * columns * options.layers - 1) * options.bombs_per_tile);
+            Board::seeded(rows, columns, bombs, options, seed).unwrap()
+        })
+}
+
+/// Every tile of the board, across all layers.
+fn tiles(board: &Board) -> Vec<(usize, usize)> {
+    let rows = (board.tile_rows * board.layers()) as usize;
+    (0..rows).flat_map(|i| (0..board.tile_columns as usize).map(move |j| (i, j))).collect()
+}
+
+fn safe_tiles(board: &Board) -> Vec<(usize, usize)> {
+    tiles(board).into_iter().filter(|(i, j)| !board.tile(*i, *j).is_bomb()).collect()
+}
+
+fn revealed(board: &Board) -> BTreeSet<(usize, usize)> {
+    tiles(board)
+        .into_iter()
+        .filter(|(i, j)| board.tile(*i, *j).state() == TileState::Revealed)
+        .collect()
+}
+
+fn adjacent(board: &Board, i: usize, j: usize) -> u32 {
+    match board.tile(i, j).value() {
+        TileValue::Adjacent(x) => x,
+        TileValue::Bomb(_) => panic!("({i}, {j}) is a bomb"),
+    }
+}
+
+/// Everything a click on a safe tile should open up on a board without flags: the tile itself,
+/// and the neighbours of every opened tile without bombs around it.
+fn flood(board: &Board, i: usize, j: usize) -> BTreeSet<(usize, usize)> {
+    let mut opened = BTreeSet::from([(i, j)]);
+    let mut queue = VecDeque::from([(i, j)]);
+    while let Some((i, j)) = queue.pop_front() {
+        if adjacent(board, i, j) != 0 {
+            continue;
+        }
+        for neighbour in board.neighbours(i, j) {
+            if opened.insert(neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    opened
+}
+
+fn flag(board: &mut Board, i: usize, j: usize, count: u32) {
+    for _ in 0..count {
+        board.resolve_flag(i, j);
+    }
+}
+
+fn json(board: &Board) -> String {
+    serde_json::to_string(board).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn places_every_bomb_that_fits(rows in 1..=20u32, columns in 1..=20u32, bombs in 0..=500u32, options in options(), seed: u64) {
+        let capacity = rows * columns * options.layers * options.bombs_per_tile;
+        let board = match Board::seeded(rows, columns, bombs, options, seed) {
+            Ok(board) => board,
+            Err(_) => {
+                prop_assert!(bombs > capacity);
+                return Ok(());
+            },
+        };
+        prop_assert!(bombs <= capacity);
+        let placed: u32 = tiles(&board).iter().map(|(i, j)| board.tile(*i, *j).bombs()).sum();
+        prop_assert_eq!(placed, bombs);
+        for (i, j) in tiles(&board) {
+            prop_assert!(board.tile(i, j).bombs() <= options.bombs_per_tile);
+        }
+    }
+
+    #[test]
+    fn excluded_and_masked_tiles_stay_free(
+        cells in prop::collection::vec(prop::collection::vec(any::<bool>(), 6), 1..=6),
+        exclude in prop::collection::vec((0..6usize, 0..6usize), 0..10),
+        bombs in 0..=40u32,
+        options in options(),
+    ) {
+        prop_assume!(cells.iter().flatten().any(|cell| *cell));
+        let mask = Mask::from_cells(cells.clone()).unwrap();
+        let rows = mask.rows();
+        let (exclude, cut_out): (Vec<_>, Vec<_>) = exclude
+            .into_iter()
+            .filter(|(i, _)| *i < rows as usize)
+            .partition(|(i, j)| cells[*i][*j]);
+        // excluding a tile the mask cuts out is a mistake of the caller
+        if let Some(&(i, j)) = cut_out.first() {
+            let with_cut_out = [exclude.clone(), vec![(i, j)]].concat();
+            let result = build_minefield(rows, 6, bombs, options, Some(&mask), &with_cut_out);
+            prop_assert_eq!(result.err(), Some(EngineError::CutOut(i, j)));
+        }
+        let free = (0..rows as usize * options.layers as usize)
+            .flat_map(|i| (0..6).map(move |j| (i, j)))
+            .filter(|(i, j)| cells[i % rows as usize][*j] && !exclude.contains(&(*i, *j)))
+            .count() as u32;
+        let minefield = build_minefield(rows, 6, bombs, options, Some(&mask), &exclude);
+        prop_assert_eq!(minefield.is_ok(), bombs <= free * options.bombs_per_tile);
+        if let Ok(minefield) = minefield {
+            prop_assert_eq!(minefield.bomb_total(), bombs);
+            for i in 0..minefield.rows() as usize {
+                for (j, playable) in cells[i % rows as usize].iter().enumerate() {
+                    if !playable || exclude.contains(&(i, j)) {
+                        prop_assert_eq!(minefield.bombs(i, j), 0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn safe_tile_opens_up(rows in 3..=12u32, columns in 3..=12u32, bombs in 0..=60u32, i in 0..12usize, j in 0..12usize) {
+        let (i, j) = (i % rows as usize, j % columns as usize);
+        let bombs = bombs.min(rows * columns - 9);
+        let board = Board::with_safe_tile(rows, columns, bombs, BoardOptions::default(), (i, j)).unwrap();
+        prop_assert_eq!(board.tile(i, j).value(), TileValue::Adjacent(0));
+    }
+
+    #[test]
+    fn first_click_is_protected(mut board in board(), i in 0..36usize, j in 0..12usize) {
+        let (i, j) = (i % (board.tile_rows * board.layers()) as usize, j % board.tile_columns as usize);
+        let mut copy: Board = serde_json::from_str(&serde_json::to_string(&board).unwrap()).unwrap();
+        board.protect_first_click(i, j);
+        copy.protect_first_click(i, j);
+        // both copies move the bombs to the same tiles
+        prop_assert_eq!(serde_json::to_string(&board).unwrap(), serde_json::to_string(&copy).unwrap());
+        prop_assert!(!board.tile(i, j).is_bomb());
+        let bombs: u32 = tiles(&board).iter().map(|(i, j)| board.tile(*i, *j).bombs()).sum();
+        prop_assert_eq!(bombs, board.bomb_count());
+        board.resolve_click(i, j);
+        prop_assert_ne!(board.state(), GameState::GameOver);
+    }
+
+    #[test]
+    fn numbers_count_the_surrounding_bombs(board in board()) {
+        for (i, j) in safe_tiles(&board) {
+            let bombs: u32 = board.neighbours(i, j).iter().map(|(ni, nj)| board.tile(*ni, *nj).bombs()).sum();
+            prop_assert_eq!(adjacent(&board, i, j), bombs);
+        }
+    }
+
+    #[test]
+    fn neighbours_are_mutual(board in board()) {
+        for (i, j) in tiles(&board) {
+            let neighbours = board.neighbours(i, j);
+            prop_assert!(!neighbours.contains(&(i, j)));
+            for (ni, nj) in neighbours {
+                prop_assert!(board.neighbours(ni, nj).contains(&(i, j)), "({}, {}) doesn't see ({}, {})", ni, nj, i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn a_click_floods_the_empty_area(mut board in board(), pick: prop::sample::Index) {
+        let safe = safe_tiles(&board);
+        prop_assume!(!safe.is_empty());
+        let (i, j) = safe[pick.index(safe.len())];
+        let expected = flood(&board, i, j);
+        board.resolve_click(i, j);
+        prop_assert_eq!(revealed(&board), expected);
+        let state = match revealed(&board).len() == safe.len() {
+            true => GameState::Won,
+            false => GameState::InGame,
+        };
+        prop_assert_eq!(board.state(), state);
+        prop_assert_eq!(board.check_game_state(), state);
+    }
+
+    #[test]
+    fn a_bomb_ends_the_game(mut board in board(), pick: prop::sample::Index) {
+        let bombs: Vec<_> = tiles(&board).into_iter().filter(|(i, j)| board.tile(*i, *j).is_bomb()).collect();
+        prop_assume!(!bombs.is_empty());
+        let (i, j) = bombs[pick.index(bombs.len())];
+        board.resolve_click(i, j);
+        prop_assert_eq!(board.state(), GameState::GameOver);
+        prop_assert_eq!(board.exploded(), Some((i, j)));
+
+        // nothing changes once the game is over
+        let before = json(&board);
+        for (i, j) in tiles(&board) {
+            board.resolve_click(i, j);
+            board.resolve_flag(i, j);
+            board.chord(i, j, ChordMismatch::RevealUnflagged);
+        }
+        prop_assert_eq!(json(&board), before);
+    }
+
+    #[test]
+    fn revealing_every_safe_tile_wins(mut board in board()) {
+        let safe = safe_tiles(&board);
+        prop_assume!(!safe.is_empty());
+        prop_assert_eq!(board.check_game_state(), GameState::InGame);
+        for (i, j) in safe {
+            prop_assert_ne!(board.state(), GameState::GameOver);
+            board.resolve_click(i, j);
+        }
+        prop_assert_eq!(board.state(), GameState::Won);
+        prop_assert_eq!(board.check_game_state(), GameState::Won);
+        prop_assert_eq!(board.exploded(), None);
+    }
+
+    #[test]
+    fn chording_with_the_right_flags(mut board in board(), pick: prop::sample::Index) {
+        let numbered: Vec<_> = safe_tiles(&board).into_iter().filter(|(i, j)| adjacent(&board, *i, *j) > 0).collect();
+        prop_assume!(!numbered.is_empty());
+        let (i, j) = numbered[pick.index(numbered.len())];
+        board.resolve_click(i, j);
+        // that may have been the last safe tile
+        prop_assume!(board.state() == GameState::InGame);
+        for (ni, nj) in board.neighbours(i, j) {
+            let bombs = board.tile(ni, nj).bombs();
+            flag(&mut board, ni, nj, bombs);
+        }
+        board.chord(i, j, ChordMismatch::Ignore);
+        prop_assert_ne!(board.state(), GameState::GameOver);
+        for (ni, nj) in board.neighbours(i, j) {
+            let tile = board.tile(ni, nj);
+            match tile.is_bomb() {
+                true => prop_assert_eq!(tile.state(), TileState::Flagged(tile.bombs())),
+                false => prop_assert_eq!(tile.state(), TileState::Revealed),
+            };
+        }
+    }
+
+    #[test]
+    fn chording_with_wrong_flags(mut board in board(), pick: prop::sample::Index) {
+        let per_tile = board.options().max_bombs_per_tile();
+        // a number that can be matched by flags on safe neighbours alone
+        let numbered: Vec<_> = safe_tiles(&board)
+            .into_iter()
+            .filter(|(i, j)| {
+                let safe_neighbours = board.neighbours(*i, *j).iter().filter(|(ni, nj)| !board.tile(*ni, *nj).is_bomb()).count() as u32;
+                adjacent(&board, *i, *j) > 0 && adjacent(&board, *i, *j) <= safe_neighbours * per_tile
+            })
+            .collect();
+        prop_assume!(!numbered.is_empty());
+        let (i, j) = numbered[pick.index(numbered.len())];
+        board.resolve_click(i, j);
+        // that may have been the last safe tile
+        prop_assume!(board.state() == GameState::InGame);
+        let mut missing = adjacent(&board, i, j);
+        for (ni, nj) in board.neighbours(i, j) {
+            if !board.tile(ni, nj).is_bomb() && missing > 0 {
+                let count = missing.min(per_tile);
+                flag(&mut board, ni, nj, count);
+                missing -= count;
+            }
+        }
+        board.chord(i, j, ChordMismatch::Ignore);
+        prop_assert_eq!(board.state(), GameState::GameOver);
+        let exploded = board.exploded().unwrap();
+        prop_assert!(board.tile(exploded.0, exploded.1).is_bomb());
+    }
+
+    #[test]
+    fn chording_without_enough_flags(mut board in board(), pick: prop::sample::Index) {
+        let numbered: Vec<_> = safe_tiles(&board).into_iter().filter(|(i, j)| adjacent(&board, *i, *j) > 0).collect();
+        prop_assume!(!numbered.is_empty());
+        let (i, j) = numbered[pick.index(numbered.len())];
+        board.resolve_click(i, j);
+        // that may have been the last safe tile
+        prop_assume!(board.state() == GameState::InGame);
+
+        let before = json(&board);
+        board.chord(i, j, ChordMismatch::Ignore);
+        prop_assert_eq!(json(&board), before);
+
+        // the bombs around a number can't all be flagged, so revealing the rest hits one
+        board.chord(i, j, ChordMismatch::RevealUnflagged);
+        prop_assert_eq!(board.state(), GameState::GameOver);
+    }
+
+    #[test]
+    fn boards_survive_serialization(mut board in board(), clicks in prop::collection::vec((any::<prop::sample::Index>(), any::<bool>()), 0..8)) {
+        let tiles = tiles(&board);
+        for (pick, flag) in clicks {
+            let (i, j) = tiles[pick.index(tiles.len())];
+            match flag {
+                true => board.resolve_flag(i, j),
+                false => board.resolve_click(i, j),
+            };
+        }
+        let copy: Board = serde_json::from_str(&json(&board)).unwrap();
+        prop_assert_eq!(json(&copy), json(&board));
+        prop_assert_eq!(copy.state(), board.state());
+        prop_assert_eq!(copy.options(), board.options());
+        prop_assert_eq!(copy.exploded(), board.exploded());
+        for (i, j) in tiles {
+            prop_assert_eq!(copy.tile(i, j).state(), board.tile(i, j).state());
+            prop_assert_eq!(copy.tile(i, j).value(), board.tile(i, j).value());
+        }
+    }
+}
+
+#[test]
+fn single_row() {
+    let mut board = Board::with_bombs(1, 5, &[(0, 2)], BoardOptions::default());
+    let values: Vec<_> = (0..5).map(|j| board.tile(0, j).value()).collect();
+    assert_eq!(values, [
+        TileValue::Adjacent(0),
+        TileValue::Adjacent(1),
+        TileValue::Bomb(1),
+        TileValue::Adjacent(1),
+        TileValue::Adjacent(0),
+    ]);
+    assert_eq!(board.neighbours(0, 0), [(0, 1)]);
+
+    board.resolve_click(0, 0);
+    assert_eq!(revealed(&board), BTreeSet::from([(0, 0), (0, 1)]));
+    board.resolve_click(0, 4);
+    assert_eq!(board.state(), GameState::Won);
+}
+
+#[test]
+fn single_column() {
+    let mut board = Board::with_bombs(5, 1, &[(4, 0)], BoardOptions::default());
+    assert_eq!(board.neighbours(2, 0), [(1, 0), (3, 0)]);
+    board.resolve_click(0, 0);
+    assert_eq!(revealed(&board), BTreeSet::from([(0, 0), (1, 0), (2, 0), (3, 0)]));
+    assert_eq!(board.state(), GameState::Won);
+}
+
+#[test]
+fn single_tile() {
+    assert!(Board::new(1, 1, 2).is_err());
+    let mut board = Board::new(1, 1, 0).unwrap();
+    board.resolve_click(0, 0);
+    assert_eq!(board.state(), GameState::Won);
+}
+
+#[test]
+fn moves_off_the_board_are_ignored() {
+    let options = BoardOptions {
+        layers: 2,
+        ..BoardOptions::default()
+    };
+    let mut board = Board::with_bombs(2, 3, &[(0, 0)], options);
+    for (i, j) in [(4, 0), (0, 3), (usize::MAX, usize::MAX)] {
+        assert!(!board.is_playable(i, j));
+        board.resolve_flag(i, j);
+        board.resolve_click(i, j);
+    }
+    assert!(board.is_playable(3, 2));
+    assert_eq!(board.state(), GameState::InGame);
+    assert!(revealed(&board).is_empty());
+}
+
+#[test]
+fn wrapping_single_row() {
+    let options = BoardOptions {
+        wrap: true,
+        ..BoardOptions::default()
+    };
+    let board = Board::with_bombs(1, 3, &[(0, 1)], options);
+    // left and right of the first tile are both the bomb, but it is only counted once
+    assert_eq!(board.neighbours(0, 0), [(0, 2), (0, 1)]);
+    assert_eq!(board.tile(0, 0).value(), TileValue::Adjacent(1));
+    assert_eq!(board.tile(0, 2).value(), TileValue::Adjacent(1));
+}
+
+#[test]
+fn cursor_on_a_single_row() {
+    let board = Board::with_bombs(1, 5, &[(0, 2)], BoardOptions::default());
+    let mut cursor = Cursor::new(&board, 0);
+    assert_eq!((cursor.i, cursor.j), (0, 2));
+    cursor.shift(&board, 1, 0);
+    cursor.shift(&board, -1, 0);
+    assert_eq!(cursor.i, 0);
+    cursor.shift(&board, 0, 10);
+    assert_eq!(cursor.j, 4);
+}
+
+#[test]
+fn flags_count_up_to_the_bombs_per_tile() {
+    let options = BoardOptions {
+        bombs_per_tile: 2,
+        ..BoardOptions::default()
+    };
+    let mut board = Board::with_bombs(3, 3, &[(0, 0), (0, 0), (2, 2)], options);
+    assert_eq!(board.tile(0, 0).value(), TileValue::Bomb(2));
+    assert_eq!(board.tile(1, 1).value(), TileValue::Adjacent(3));
+    let states: Vec<_> = (0..3)
+        .map(|_| {
+            board.resolve_flag(0, 0);
+            board.tile(0, 0).state()
+        })
+        .collect();
+    assert_eq!(states, [TileState::Flagged(1), TileState::Flagged(2), TileState::Hidden]);
+}
+
+#[test]
+fn question_marks_follow_the_flag() {
+    let mut board = Board::with_bombs(3, 3, &[(0, 0)], BoardOptions::default());
+    board.set_question_marks(true);
+    flag(&mut board, 2, 2, 2);
+    assert_eq!(board.tile(2, 2).state(), TileState::Questioned);
+    // a question mark doesn't stop a reveal
+    board.resolve_click(2, 2);
+    assert_eq!(board.tile(2, 2).state(), TileState::Revealed);
+}
+
+#[test]
+fn flags_stop_the_flood() {
+    let mut board = Board::with_bombs(1, 5, &[(0, 4)], BoardOptions::default());
+    board.resolve_flag(0, 1);
+    board.resolve_click(0, 0);
+    assert_eq!(revealed(&board), BTreeSet::from([(0, 0)]));
+    assert_eq!(board.tile(0, 1).state(), TileState::Flagged(1));
+}
+
+#[test]
+fn build_minefield_stacks_layers() {
+    let options = BoardOptions {
+        layers: 3,
+        ..BoardOptions::default()
+    };
+    let minefield = build_minefield(4, 5, 7, options, None, &[]).unwrap();
+    assert_eq!((minefield.rows(), minefield.columns()), (12, 5));
+    assert_eq!(minefield.bomb_total(), 7);
+}
+
+#[test]
+fn full_boards() {
+    let options = BoardOptions {
+        bombs_per_tile: 3,
+        ..BoardOptions::default()
+    };
+    let board = Board::with_options(5, 6, 90, options).unwrap();
+    assert!(tiles(&board).iter().all(|(i, j)| board.tile(*i, *j).bombs() == 3));
+    assert!(Board::with_options(5, 6, 91, options).is_err());
+}
+
+#[test]
+fn first_click_keeps_flags_and_happens_once() {
+    let mut board = Board::seeded(3, 3, 1, BoardOptions::default(), 7).unwrap();
+    let (i, j) = tiles(&board).into_iter().find(|(i, j)| board.tile(*i, *j).is_bomb()).unwrap();
+    let (fi, fj) = (2 - i, 2 - j);
+    board.resolve_flag(fi, fj);
+    board.protect_first_click(i, j);
+    assert!(!board.tile(i, j).is_bomb());
+    assert_eq!(board.tile(fi, fj).state(), TileState::Flagged(1));
+
+    // once a tile is revealed the bombs stay where they are
+    board.resolve_click(i, j);
+    let before = serde_json::to_string(&board).unwrap();
+    board.protect_first_click(fi, fj);
+    assert_eq!(serde_json::to_string(&board).unwrap(), before);
+}
+
+#[test]
+fn first_click_leaves_placed_bombs_alone() {
+    let mut board = Board::with_bombs(3, 3, &[(1, 1)], BoardOptions::default());
+    board.protect_first_click(1, 1);
+    assert!(board.tile(1, 1).is_bomb());
+    board.apply(Action::Reveal(1, 1));
+    assert_eq!(board.state(), GameState::GameOver);
+}
+
+#[test]
+fn first_click_on_a_masked_board() {
+    let mask = Mask::from_cells(vec![vec![true, true, false], vec![true, true, true]]).unwrap();
+    let mut board = Board::with_mask(4, BoardOptions::default(), mask).unwrap();
+    board.protect_first_click(0, 0);
+    assert!(!board.tile(0, 0).is_bomb());
+    assert!(!board.tile(0, 2).is_bomb());
+    assert_eq!(safe_tiles(&board).into_iter().filter(|(i, j)| board.is_playable(*i, *j)).count(), 1);
+}
+
+#[test]
+fn safe_tile_falls_back_to_the_tile_alone() {
+    // the neighbours of a corner and the corner itself are 4 of 25 tiles
+    let board = Board::with_safe_tile(5, 5, 24, BoardOptions::default(), (0, 0)).unwrap();
+    assert_eq!(safe_tiles(&board), [(0, 0)]);
+    assert!(Board::with_safe_tile(5, 5, 25, BoardOptions::default(), (0, 0)).is_err());
+    assert!(Board::with_safe_tile(5, 5, 1, BoardOptions::default(), (5, 0)).is_err());
+}
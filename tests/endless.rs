@@ -0,0 +1,90 @@
+//! Tests of the endless field: chunks that are packed away once the camera leaves them have to
+//! come back just as the player left them.
+
+use game_clones::minesweeper::board::TileState;
+use game_clones::minesweeper::endless::{EndlessBoard, CHUNK_SIZE};
+
+const SEED: u64 = 2024;
+const DENSITY: u32 = 20;
+
+/// The tiles of the chunks around the origin, row by row.
+fn snapshot(field: &EndlessBoard) -> Vec<TileState> {
+    let range = -2 * CHUNK_SIZE..2 * CHUNK_SIZE;
+    range.clone().flat_map(|y| range.clone().map(move |x| (x, y))).map(|(x, y)| field.state(x, y)).collect()
+}
+
+/// Moves the camera far away, which packs every chunk.
+fn go_away(field: &mut EndlessBoard) {
+    field.evict(1000 * CHUNK_SIZE, 1000 * CHUNK_SIZE);
+    assert_eq!(field.loaded_chunks(), 0);
+}
+
+#[test]
+fn partly_cleared_chunks_keep_their_reveals_and_flags() {
+    let mut field = EndlessBoard::new(SEED, DENSITY);
+    field.resolve_click(0, 0);
+    let hidden: Vec<(i64, i64)> = (0..CHUNK_SIZE)
+        .flat_map(|y| (0..CHUNK_SIZE).map(move |x| (x, y)))
+        .filter(|&(x, y)| field.state(x, y) == TileState::Hidden)
+        .take(2)
+        .collect();
+    for &(x, y) in &hidden {
+        field.resolve_flag(x, y);
+    }
+    let (before, score) = (snapshot(&field), field.score());
+
+    go_away(&mut field);
+    assert_eq!(snapshot(&field), before);
+
+    // taking a flag off brings its chunk back in full, with the rest as it was
+    let (x, y) = hidden[0];
+    field.resolve_flag(x, y);
+    assert_eq!(field.state(x, y), TileState::Hidden);
+    field.resolve_flag(x, y);
+    assert_eq!(snapshot(&field), before);
+    assert_eq!(field.score(), score);
+}
+
+#[test]
+fn cleared_chunks_keep_their_bombs_hidden() {
+    let mut field = EndlessBoard::new(SEED, DENSITY);
+    // clear a chunk away from the origin by hand, flagging a single one of its bombs
+    let start = 3 * CHUNK_SIZE;
+    let tiles: Vec<(i64, i64)> = (start..start + CHUNK_SIZE).flat_map(|y| (start..start + CHUNK_SIZE).map(move |x| (x, y))).collect();
+    for &(x, y) in &tiles {
+        if !field.is_bomb(x, y) {
+            field.resolve_click(x, y);
+        }
+    }
+    let bombs: Vec<(i64, i64)> = tiles.iter().copied().filter(|&(x, y)| field.is_bomb(x, y)).collect();
+    let flagged = bombs[0];
+    field.resolve_flag(flagged.0, flagged.1);
+    let score = field.score();
+
+    go_away(&mut field);
+    assert_eq!(field.state(flagged.0, flagged.1), TileState::Flagged(1));
+    for &(x, y) in &bombs[1..] {
+        assert_eq!(field.state(x, y), TileState::Hidden, "({x}, {y})");
+    }
+    assert!(tiles.iter().filter(|&&(x, y)| !field.is_bomb(x, y)).all(|&(x, y)| field.state(x, y) == TileState::Revealed));
+
+    // and the same once the chunk is back in memory
+    field.resolve_flag(flagged.0, flagged.1);
+    assert_eq!(field.state(flagged.0, flagged.1), TileState::Hidden);
+    assert_eq!(field.loaded_chunks(), 1);
+    assert_eq!(field.score(), score);
+}
+
+#[test]
+fn far_chunks_are_packed_however_little_was_cleared() {
+    let mut field = EndlessBoard::new(SEED, DENSITY);
+    for chunk in 0..20 {
+        // a single flag in each chunk along a row, none of them cleared
+        field.resolve_flag(chunk * CHUNK_SIZE, 0);
+        field.evict(chunk * CHUNK_SIZE, 0);
+    }
+    assert!(field.loaded_chunks() <= 5, "{} chunks loaded", field.loaded_chunks());
+    for chunk in 0..20 {
+        assert_eq!(field.state(chunk * CHUNK_SIZE, 0), TileState::Flagged(1));
+    }
+}
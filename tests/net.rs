@@ -0,0 +1,422 @@
+//! Plays whole games against the minesweeper server on the loopback interface. Every test starts its
+//! own server on an ephemeral port in a thread, so they run without SDL and in parallel.
+
+use std::net::{SocketAddr, UdpSocket};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use proptest::prelude::*;
+use game_clones::minesweeper::board::{Action, Board, GameState, TileState, TileValue};
+use game_clones::minesweeper::error::{Error, ProtocolError};
+use game_clones::minesweeper::net::{Connection, Limits, Server};
+
+/// Long enough for any answer on loopback, short enough that a server that stays quiet fails the
+/// test instead of hanging it.
+const TIMEOUT: Duration = Duration::from_secs(5);
+const ROWS: u32 = 8;
+const COLUMNS: u32 = 10;
+const BOMBS: u32 = 12;
+
+fn start_server() -> (SocketAddr, JoinHandle<Result<(), Error>>) {
+    start_server_with(Limits::default())
+}
+
+fn start_server_with(limits: Limits) -> (SocketAddr, JoinHandle<Result<(), Error>>) {
+    let server = Server::with_limits("127.0.0.1:0", limits).unwrap();
+    let address = server.local_addr().unwrap();
+    (address, thread::spawn(move || server.serve()))
+}
+
+/// A client that speaks the protocol by hand, so the tests see every message as it goes over the wire.
+struct Client {
+    socket: UdpSocket,
+}
+
+impl Client {
+    fn connect(address: SocketAddr) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(TIMEOUT)).unwrap();
+        socket.connect(address).unwrap();
+        Self { socket }
+    }
+
+    fn send(&self, message: &str) {
+        self.send_bytes(message.as_bytes());
+    }
+
+    fn send_bytes(&self, message: &[u8]) {
+        self.socket.send(message).unwrap();
+    }
+
+    fn receive(&self) -> String {
+        let mut buf = vec![0; 65507];
+        let amt = self.socket.recv(&mut buf).expect("no answer from the server");
+        String::from_utf8(buf[..amt].to_vec()).unwrap()
+    }
+
+    fn request(&self, message: &str) -> String {
+        self.send(message);
+        self.receive()
+    }
+
+    /// Asks for a board, checks it is the one asked for and still untouched.
+    fn start(&self, rows: u32, columns: u32, bombs: u32, wrap: bool) -> Board {
+        let board: Board = serde_json::from_str(&self.request(&format!("[0,{rows},{columns},{bombs},{wrap}]"))).unwrap();
+        assert_eq!((board.tile_rows, board.tile_columns, board.bomb_count()), (rows, columns, bombs));
+        assert_eq!(board.options().wrap, wrap);
+        assert_eq!(board.state(), GameState::InGame);
+        board
+    }
+}
+
+fn tiles(board: &Board) -> impl Iterator<Item = (usize, usize)> {
+    let (rows, columns) = (board.tile_rows as usize, board.tile_columns as usize);
+    (0..rows).flat_map(move |i| (0..columns).map(move |j| (i, j)))
+}
+
+fn bomb(board: &Board) -> (usize, usize) {
+    tiles(board).find(|&(i, j)| board.tile(i, j).is_bomb()).unwrap()
+}
+
+fn reveal(i: usize, j: usize) -> String {
+    format!(r#"{{"Reveal":[{i},{j}]}}"#)
+}
+
+/// Makes the first reveal, which may move bombs away from it, on the server and on `board`.
+fn open(client: &Client, board: &mut Board) {
+    assert_eq!(client.request(&reveal(0, 0)), "true");
+    board.apply(Action::Reveal(0, 0));
+}
+
+#[test]
+fn win() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    let mut board = client.start(ROWS, COLUMNS, BOMBS, false);
+    open(&client, &mut board);
+
+    let (i, j) = bomb(&board);
+    assert_eq!(client.request(&format!(r#"{{"ToggleFlag":[{i},{j}]}}"#)), "true");
+    board.apply(Action::ToggleFlag(i as u32, j as u32));
+
+    for (i, j) in tiles(&board).collect::<Vec<_>>() {
+        if board.state() == GameState::InGame && matches!(board.tile(i, j).value(), TileValue::Adjacent(_)) {
+            assert_eq!(client.request(&reveal(i, j)), "true");
+            board.apply(Action::Reveal(i as u32, j as u32));
+        }
+    }
+    assert_eq!(board.state(), GameState::Won);
+
+    assert_eq!(client.request(r#""Won""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn lose_and_quit() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    let mut board = client.start(ROWS, COLUMNS, BOMBS, true);
+    open(&client, &mut board);
+
+    let (i, j) = bomb(&board);
+    assert_eq!(client.request(&reveal(i, j)), "true");
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn quit_during_the_game() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    client.start(ROWS, COLUMNS, BOMBS, false);
+
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn won_during_the_game_is_rejected() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    client.start(ROWS, COLUMNS, BOMBS, false);
+
+    assert_eq!(client.request(r#""Won""#), "false");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn moves_after_losing_are_rejected() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    let mut board = client.start(ROWS, COLUMNS, BOMBS, false);
+    open(&client, &mut board);
+
+    let (i, j) = bomb(&board);
+    assert_eq!(client.request(&reveal(i, j)), "true");
+    assert_eq!(client.request(&reveal(0, 0)), "false");
+    assert_eq!(client.request(r#"{"ToggleFlag":[0,0]}"#), "false");
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn moves_off_the_board_are_rejected() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    client.start(ROWS, COLUMNS, BOMBS, true);
+
+    for (i, j) in [(ROWS, 0), (0, COLUMNS), (ROWS, COLUMNS), (u32::MAX, u32::MAX)] {
+        assert_eq!(client.request(&reveal(i as usize, j as usize)), "false", "({i}, {j})");
+        assert_eq!(client.request(&format!(r#"{{"ToggleFlag":[{i},{j}]}}"#)), "false", "({i}, {j})");
+    }
+    assert_eq!(client.request(&reveal(0, 0)), "true");
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn won_after_losing_is_rejected() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+    let mut board = client.start(ROWS, COLUMNS, BOMBS, false);
+    open(&client, &mut board);
+
+    let (i, j) = bomb(&board);
+    assert_eq!(client.request(&reveal(i, j)), "true");
+    assert_eq!(client.request(r#""Won""#), "false");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn malformed_packets_are_turned_down() {
+    let (address, server) = start_server();
+    let client = Client::connect(address);
+
+    // board requests that can't be read are turned down, and the server waits for another one
+    assert_eq!(client.request("hello"), "false");
+    client.send_bytes(&[0xff, 0xfe, 0x00]);
+    assert_eq!(client.receive(), "false");
+    assert_eq!(client.request("[0,8,10]"), "false");
+    client.start(ROWS, COLUMNS, BOMBS, false);
+
+    for message in ["", "{", r#"{"Reveal":[1]}"#, r#"{"Reveal":[-1,2]}"#, r#""Surrender""#, "null", "[0,8,10,12,false]"] {
+        assert_eq!(client.request(message), "false", "{message}");
+    }
+    assert_eq!(client.request(&"[".repeat(65000)), "false");
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn settings_beyond_the_limits_are_turned_down() {
+    let (address, server) = start_server_with(Limits { max_tiles: 100, max_bombs: 20, ..Limits::default() });
+    let client = Client::connect(address);
+
+    for message in ["[0,0,10,12,false]", "[0,8,10,0,false]", "[0,8,10,80,false]", "[0,200,200,100,false]", "[0,10,11,12,false]", "[0,8,10,21,false]"] {
+        assert_eq!(client.request(message), "false", "{message}");
+    }
+    client.start(ROWS, COLUMNS, 20, false);
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn largest_board_fits_in_a_datagram() {
+    let (address, server) = start_server();
+    let limits = Limits::default();
+    let (connection, board) = Connection::start(address, 100, limits.max_tiles / 100, limits.max_bombs, true).unwrap();
+    assert_eq!(board.bomb_count(), limits.max_bombs);
+
+    assert!(connection.validate(Action::Quit).unwrap());
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn connection_reports_a_rejected_board() {
+    let (address, _server) = start_server();
+    assert!(matches!(Connection::start(address, 1000, 1000, 10, false), Err(Error::Protocol(ProtocolError::Rejected))));
+}
+
+#[test]
+fn connection_gives_up_on_a_server_that_is_not_there() {
+    let address = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    assert!(matches!(Connection::start(address, ROWS, COLUMNS, BOMBS, false), Err(Error::Protocol(ProtocolError::NoAnswer))));
+}
+
+#[test]
+fn connection_gives_up_on_a_quiet_server() {
+    // bound, so the request arrives, but nothing ever answers it
+    let quiet = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = quiet.local_addr().unwrap();
+    assert!(matches!(Connection::start(address, ROWS, COLUMNS, BOMBS, false), Err(Error::Protocol(ProtocolError::NoAnswer))));
+}
+
+#[test]
+fn moves_beyond_the_rate_limit_are_rejected() {
+    let (address, server) = start_server_with(Limits { burst: 3, moves_per_second: 1, ..Limits::default() });
+    let client = Client::connect(address);
+    let board = client.start(ROWS, COLUMNS, BOMBS, false);
+    let (i, j) = bomb(&board);
+
+    let flag = format!(r#"{{"ToggleFlag":[{i},{j}]}}"#);
+    for _ in 0..3 {
+        assert_eq!(client.request(&flag), "true");
+    }
+    assert_eq!(client.request(&flag), "false");
+    // a message that can't be read costs a move as well
+    thread::sleep(Duration::from_millis(1100));
+    assert_eq!(client.request("{"), "false");
+    assert_eq!(client.request(&flag), "false");
+
+    thread::sleep(Duration::from_millis(1100));
+    assert_eq!(client.request(&flag), "true");
+    // quitting is always possible
+    assert_eq!(client.request(r#""Quit""#), "true");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn quiet_clients_are_given_up_on() {
+    let server = Server::with_limits("127.0.0.1:0", Limits { idle_timeout: Duration::from_millis(100), ..Limits::default() }).unwrap();
+    let address = server.local_addr().unwrap();
+    let server = thread::spawn(move || (server.serve(), server.serve()));
+
+    let quiet = Client::connect(address);
+    quiet.start(ROWS, COLUMNS, BOMBS, false);
+    // the next client is only served once the quiet one was given up on
+    thread::sleep(Duration::from_millis(200));
+    let client = Client::connect(address);
+    client.start(ROWS, COLUMNS, BOMBS, false);
+    assert_eq!(client.request(r#""Quit""#), "true");
+
+    let (first, second) = server.join().unwrap();
+    assert!(matches!(first, Err(Error::Io(_))));
+    second.unwrap();
+}
+
+#[test]
+fn serves_clients_one_after_another() {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let server = thread::spawn(move || (server.serve(), server.serve()));
+
+    let first = Client::connect(address);
+    first.start(ROWS, COLUMNS, BOMBS, false);
+    // a second client is turned down while the first one plays
+    let second = Client::connect(address);
+    assert_eq!(second.request(&format!("[0,{ROWS},{COLUMNS},{BOMBS},false]")), "false");
+    assert_eq!(first.request(&reveal(0, 0)), "true");
+    assert_eq!(first.request(r#""Quit""#), "true");
+
+    let mut board = second.start(ROWS, COLUMNS, BOMBS, true);
+    open(&second, &mut board);
+    let (i, j) = bomb(&board);
+    assert_eq!(second.request(&reveal(i, j)), "true");
+    assert_eq!(second.request(r#""Quit""#), "true");
+
+    let (first, second) = server.join().unwrap();
+    first.unwrap();
+    second.unwrap();
+}
+
+#[test]
+fn connection_plays_a_whole_game() {
+    let (address, server) = start_server();
+    let (connection, mut board) = Connection::start(address, ROWS, COLUMNS, BOMBS, false).unwrap();
+    assert_eq!((board.tile_rows, board.tile_columns, board.bomb_count()), (ROWS, COLUMNS, BOMBS));
+
+    // the bombs move away from the first reveal, on both copies of the board alike
+    let (i, j) = bomb(&board);
+    assert!(connection.play(&mut board, Action::Reveal(i as u32, j as u32)).unwrap());
+    assert!(!board.tile(i, j).is_bomb());
+    assert_eq!(board.bomb_count(), BOMBS);
+
+    for (i, j) in tiles(&board).collect::<Vec<_>>() {
+        if board.state() == GameState::InGame && !board.tile(i, j).is_bomb() {
+            assert!(connection.play(&mut board, Action::Reveal(i as u32, j as u32)).unwrap());
+        }
+    }
+    assert_eq!(board.state(), GameState::Won);
+
+    assert!(connection.validate(Action::Won).unwrap());
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn chording_a_hidden_tile_keeps_the_boards_in_sync() {
+    let (address, server) = start_server();
+    let (connection, mut board) = Connection::start(address, ROWS, COLUMNS, BOMBS, false).unwrap();
+    let (i, j) = tiles(&board).find(|&(i, j)| !board.tile(i, j).is_bomb()).unwrap();
+
+    // the terminal front-end sends a chord as a reveal, which opens a hidden tile on the server
+    let mut server_board: Board = serde_json::from_str(&serde_json::to_string(&board).unwrap()).unwrap();
+    server_board.apply(Action::Reveal(i as u32, j as u32));
+    assert!(connection.play(&mut board, Action::Reveal(i as u32, j as u32)).unwrap());
+    assert_eq!(board.tile(i, j).state(), TileState::Revealed);
+    assert_eq!(serde_json::to_string(&board).unwrap(), serde_json::to_string(&server_board).unwrap());
+
+    // both copies agree on what is left to reveal, so the server takes the win
+    for (i, j) in tiles(&board).collect::<Vec<_>>() {
+        if board.state() == GameState::InGame && !board.tile(i, j).is_bomb() && board.tile(i, j).state() != TileState::Revealed {
+            assert!(connection.play(&mut board, Action::Reveal(i as u32, j as u32)).unwrap());
+        }
+    }
+    assert_eq!(board.state(), GameState::Won);
+    assert!(connection.validate(Action::Won).unwrap());
+    server.join().unwrap().unwrap();
+}
+
+/// Anything a client might send: actions on and off the board, the other messages of the protocol
+/// and random text and bytes.
+fn packet() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        (0..ROWS + 2, 0..COLUMNS + 2).prop_map(|(i, j)| reveal(i as usize, j as usize).into_bytes()),
+        (any::<u32>(), any::<u32>()).prop_map(|(i, j)| format!(r#"{{"ToggleFlag":[{i},{j}]}}"#).into_bytes()),
+        (any::<i64>(), any::<i64>()).prop_map(|(i, j)| format!(r#"{{"Reveal":[{i},{j}]}}"#).into_bytes()),
+        Just(br#"[0,8,10,12,false]"#.to_vec()),
+        "\\PC{0,40}".prop_map(String::into_bytes),
+        prop::collection::vec(any::<u8>(), 0..200),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    /// Whatever comes in during a game, every packet gets an answer and the game still ends cleanly.
+    #[test]
+    fn random_packets_are_answered(packets in prop::collection::vec(packet(), 1..50)) {
+        let (address, server) = start_server();
+        let client = Client::connect(address);
+        client.start(ROWS, COLUMNS, BOMBS, false);
+
+        for packet in &packets {
+            client.send_bytes(packet);
+            let answer = client.receive();
+            prop_assert!(answer == "true" || answer == "false", "{answer}");
+        }
+        prop_assert_eq!(client.request(r#""Quit""#), "true");
+        prop_assert!(server.join().unwrap().is_ok());
+    }
+
+    /// Random board requests either get a board or are turned down, and a good one still gets through.
+    #[test]
+    fn random_settings_are_answered(settings in prop::collection::vec((any::<u32>(), 0..300u32, 0..300u32, 0..10_000u32, any::<bool>()), 1..10)) {
+        let (address, server) = start_server();
+        let client = Client::connect(address);
+
+        let mut started = false;
+        for (size, rows, columns, bombs, wrap) in settings {
+            let answer = client.request(&format!("[{size},{rows},{columns},{bombs},{wrap}]"));
+            if answer != "false" {
+                let board: Board = serde_json::from_str(&answer).unwrap();
+                prop_assert_eq!((board.tile_rows, board.tile_columns, board.bomb_count()), (rows, columns, bombs));
+                started = true;
+                break;
+            }
+        }
+        if !started {
+            client.start(ROWS, COLUMNS, BOMBS, false);
+        }
+        prop_assert_eq!(client.request(r#""Quit""#), "true");
+        prop_assert!(server.join().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+//! Renders known boards and game states headlessly and compares them to the golden images in
+//! `tests/golden`. A missing image fails its case; `UPDATE_GOLDEN=1 cargo test --features sdl`
+//! records all of them, for a new case or after an intended change to the look of the games.
+//!
+//! SDL may only be used from one thread, so this runs without the test harness, one case after another.
+
+use std::path::PathBuf;
+use sdl2::rect::Rect;
+use game_clones::app::{App, Assets, WindowSettings};
+use game_clones::golden;
+use game_clones::minesweeper::board::{Board, BoardOptions, Topology};
+use game_clones::minesweeper::camera::Camera;
+use game_clones::minesweeper::layout::Layout;
+use game_clones::minesweeper::render::{board_textures, draw_game, hud_width, view_size, HUD_HEIGHT};
+use game_clones::minesweeper::theme::builtin_themes;
+use game_clones::space_invaders::{self, SpaceInvaders, WINDOW_HEIGHT, WINDOW_WIDTH};
+
+const TILE_SIZE: u32 = 20;
+/// Bombs of the 9 by 9 boards, in the spots the pictures are easy to check by eye.
+const BOMBS: [(usize, usize); 10] = [(0, 0), (0, 8), (2, 3), (4, 4), (4, 5), (5, 8), (6, 1), (7, 7), (8, 2), (8, 8)];
+const CLASSIC: usize = 0;
+const DARK: usize = 1;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{name}.png"))
+}
+
+fn headless(width: u32, height: u32) -> Result<App, String> {
+    App::headless(WindowSettings {
+        title: "golden",
+        width,
+        height,
+        resizable: false,
+    })
+}
+
+/// Draws a board as the game shows it, HUD included and with the whole board in view.
+fn check_board(name: &str, board: &Board, theme: usize) -> Result<(), String> {
+    check_board_at(name, board, theme, TILE_SIZE)
+}
+
+fn check_board_at(name: &str, board: &Board, theme: usize, tile_size: u32) -> Result<(), String> {
+    let layout = Layout::new(board, tile_size);
+    let (width, height) = view_size(board, &layout);
+    let mut app = headless(hud_width(width), height + HUD_HEIGHT)?;
+    let assets = Assets::new(&app.canvas);
+    let textures = board_textures(&mut app.canvas, assets.creator(), &builtin_themes()[theme], tile_size)?;
+    let camera = Camera::new(Rect::new(0, HUD_HEIGHT as i32, width, height), (width, height));
+    draw_game(&mut app.canvas, board, &layout, &textures, board.state(), &[], false, 0, 0, &camera, None)?;
+    golden::check(&app.screenshot()?, &golden_path(name))
+}
+
+fn check_space_invaders(name: &str, game: &SpaceInvaders) -> Result<(), String> {
+    let mut app = headless(WINDOW_WIDTH, WINDOW_HEIGHT)?;
+    let assets = Assets::new(&app.canvas);
+    let textures = space_invaders::textures(&mut app.canvas, assets.creator())?;
+    space_invaders::draw(&mut app.canvas, game, &textures)?;
+    golden::check(&app.screenshot()?, &golden_path(name))
+}
+
+fn board() -> Board {
+    Board::with_bombs(9, 9, &BOMBS, BoardOptions::default())
+}
+
+fn minesweeper_hidden() -> Result<(), String> {
+    check_board("minesweeper_hidden", &board(), CLASSIC)
+}
+
+fn minesweeper_in_game() -> Result<(), String> {
+    let mut board = board();
+    board.set_question_marks(true);
+    // opens up the empty top right area
+    board.resolve_click(2, 7);
+    board.resolve_flag(0, 8);
+    board.resolve_flag(8, 8);
+    board.resolve_flag(8, 8);
+    check_board("minesweeper_in_game", &board, CLASSIC)
+}
+
+fn minesweeper_game_over() -> Result<(), String> {
+    let mut board = board();
+    board.resolve_click(2, 7);
+    board.resolve_flag(0, 0);
+    board.resolve_flag(1, 1);
+    board.resolve_click(4, 4);
+    check_board("minesweeper_game_over", &board, CLASSIC)
+}
+
+fn minesweeper_won() -> Result<(), String> {
+    let mut board = board();
+    for i in 0..9 {
+        for j in 0..9 {
+            if !BOMBS.contains(&(i, j)) {
+                board.resolve_click(i, j);
+            }
+        }
+    }
+    check_board("minesweeper_won", &board, CLASSIC)
+}
+
+fn minesweeper_hex() -> Result<(), String> {
+    let options = BoardOptions {
+        topology: Topology::Hex,
+        ..BoardOptions::default()
+    };
+    let mut board = Board::with_bombs(9, 9, &BOMBS, options);
+    board.resolve_click(2, 7);
+    board.resolve_flag(0, 8);
+    check_board("minesweeper_hex", &board, DARK)
+}
+
+fn minesweeper_layers() -> Result<(), String> {
+    let options = BoardOptions {
+        layers: 2,
+        ..BoardOptions::default()
+    };
+    let mut bombs = BOMBS.to_vec();
+    bombs.extend(BOMBS.iter().map(|(i, j)| (i + 9, 8 - j)));
+    let mut board = Board::with_bombs(9, 9, &bombs, options);
+    board.resolve_click(8, 5);
+    check_board("minesweeper_layers", &board, CLASSIC)
+}
+
+/// The narrowest board there is at the smallest tile size, which is narrower than the HUD.
+fn minesweeper_narrow() -> Result<(), String> {
+    let mut board = Board::with_bombs(5, 5, &[(0, 0), (2, 3), (4, 1)], BoardOptions::default());
+    board.resolve_click(0, 4);
+    board.resolve_flag(0, 0);
+    check_board_at("minesweeper_narrow", &board, CLASSIC, 8)
+}
+
+fn space_invaders_start() -> Result<(), String> {
+    check_space_invaders("space_invaders_start", &SpaceInvaders::new(10))
+}
+
+fn space_invaders_shooting() -> Result<(), String> {
+    let mut game = SpaceInvaders::new(10);
+    for step in 0..240 {
+        if step % 40 == 0 {
+            game.shoot();
+        }
+        game.update_player(0, 1);
+        game.update();
+    }
+    check_space_invaders("space_invaders_shooting", &game)
+}
+
+type Case = fn() -> Result<(), String>;
+
+fn main() {
+    let cases: [(&str, Case); 9] = [
+        ("minesweeper_hidden", minesweeper_hidden),
+        ("minesweeper_in_game", minesweeper_in_game),
+        ("minesweeper_game_over", minesweeper_game_over),
+        ("minesweeper_won", minesweeper_won),
+        ("minesweeper_hex", minesweeper_hex),
+        ("minesweeper_layers", minesweeper_layers),
+        ("minesweeper_narrow", minesweeper_narrow),
+        ("space_invaders_start", space_invaders_start),
+        ("space_invaders_shooting", space_invaders_shooting),
+    ];
+    let mut failed = 0;
+    for (name, case) in cases {
+        match case() {
+            Ok(()) => println!("{name} ... ok"),
+            Err(e) => {
+                println!("{name} ... FAILED: {e}");
+                failed += 1;
+            },
+        }
+    }
+    if failed > 0 {
+        println!("{failed} of {} rendering tests failed", cases.len());
+        std::process::exit(1);
+    }
+}